@@ -0,0 +1,151 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::Palette;
+use crate::hooks::FractalType;
+use crate::location::Location;
+
+/// A named location: enough to bring any pane back to the same picture.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    #[serde(flatten)]
+    pub location: Location,
+}
+
+/// On disk the list is a series of `[[bookmark]]` tables.
+#[derive(Serialize, Deserialize)]
+struct BookmarkFile {
+    #[serde(default)]
+    bookmark: Vec<Bookmark>,
+}
+
+/// `bookmarks.toml` in the user data directory, or the working directory when there is none.
+pub fn default_path() -> PathBuf {
+    match dirs::data_dir() {
+        Some(dir) => dir.join("frac-tui-rs").join("bookmarks.toml"),
+        None => PathBuf::from("frac-tui-bookmarks.toml"),
+    }
+}
+
+/// Reads the bookmark file, starting from the built-in locations when there is none yet.
+/// Entries outside the view limits are left out and described in the second list, so one
+/// bad hand edit does not cost the rest.
+pub fn load(path: &Path) -> io::Result<(Vec<Bookmark>, Vec<String>)> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((seeds(), Vec::new())),
+        Err(e) => return Err(e),
+    };
+    parse(&text)
+}
+
+fn parse(text: &str) -> io::Result<(Vec<Bookmark>, Vec<String>)> {
+    let file: BookmarkFile = toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut skipped = Vec::new();
+    let bookmarks = file
+        .bookmark
+        .into_iter()
+        .filter(|bookmark| match bookmark.location.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                skipped.push(format!("\"{}\": {}", bookmark.name, e));
+                false
+            }
+        })
+        .collect();
+    Ok((bookmarks, skipped))
+}
+
+pub fn save(path: &Path, bookmarks: &[Bookmark]) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = BookmarkFile { bookmark: bookmarks.to_vec() };
+    let text = toml::to_string_pretty(&file).map_err(io::Error::other)?;
+    fs::write(path, text)
+}
+
+fn seeds() -> Vec<Bookmark> {
+    let mandelbrot = |center_x, center_y, scale| Location {
+        fractal_type: FractalType::Mandelbrot,
+        center_x,
+        center_y,
+        scale,
+        max_iters: 1100,
+        palette: Palette::Classic,
+        julia_cx: -0.5125,
+        julia_cy: 0.5213,
+    };
+    let seed = |name: &str, location| Bookmark { name: name.to_string(), location };
+    vec![
+        seed("Seahorse Valley", mandelbrot(-0.7463, 0.1102, 0.01)),
+        seed("Elephant Valley", mandelbrot(0.2925, 0.0149, 0.02)),
+        seed("Triple Spiral Valley", mandelbrot(-0.0880, 0.6547, 0.01)),
+        seed("Mini Mandelbrot", mandelbrot(-1.7499, 0.0, 0.035)),
+        seed("Burning Ship", Location { fractal_type: FractalType::BurningShip, ..mandelbrot(-1.755, -0.03, 0.1) }),
+        seed("Douady Rabbit", Location {
+            fractal_type: FractalType::Julia,
+            julia_cx: -0.123,
+            julia_cy: 0.745,
+            ..mandelbrot(0.0, 0.0, 2.6)
+        }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_outside_the_view_limits_are_skipped() {
+        let text = r#"
+            [[bookmark]]
+            name = "Flat"
+            fractal_type = "Mandelbrot"
+            center_x = -0.75
+            center_y = 0.0
+            scale = 0.0
+            max_iters = 100
+            palette = "Classic"
+            julia_cx = 0.0
+            julia_cy = 0.0
+
+            [[bookmark]]
+            name = "Shallow"
+            fractal_type = "Mandelbrot"
+            center_x = -0.75
+            center_y = 0.0
+            scale = 3.0
+            max_iters = 5
+            palette = "Classic"
+            julia_cx = 0.0
+            julia_cy = 0.0
+
+            [[bookmark]]
+            name = "Whole set"
+            fractal_type = "Mandelbrot"
+            center_x = -0.75
+            center_y = 0.0
+            scale = 3.0
+            max_iters = 100
+            palette = "Classic"
+            julia_cx = 0.0
+            julia_cy = 0.0
+        "#;
+        let (bookmarks, skipped) = parse(text).unwrap();
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks[0].name, "Whole set");
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped[0].starts_with("\"Flat\": "));
+        assert!(skipped[1].starts_with("\"Shallow\": "));
+    }
+
+    #[test]
+    fn seeds_are_inside_the_view_limits() {
+        assert!(seeds().iter().all(|bookmark| bookmark.location.validate().is_ok()));
+    }
+}
@@ -0,0 +1,220 @@
+use std::io;
+use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use crate::color::{ColorSupport, Palette};
+use crate::config::Defaults;
+use crate::export::{parse_size, ImageFormat};
+use crate::graphics::GraphicsProtocol;
+use crate::hooks::{FractalPane, FractalType, Layout};
+use crate::interop;
+use crate::location::Location;
+use crate::render::RenderMode;
+
+#[derive(Parser, Debug)]
+#[command(name = "frac-tui-rs", version, about = "a practice tui for ratatui")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Starting view of the TUI; new panes and reset use it too
+    #[command(flatten)]
+    pub view: ViewArgs,
+
+    /// Start with a split arrangement: single, columns, rows, grid or main-stack
+    #[arg(long, default_value = "single", conflicts_with = "session")]
+    pub layout: Layout,
+
+    /// Override color detection: truecolor, 256 or 16
+    #[arg(long, value_name = "MODE", global = true)]
+    pub colors: Option<ColorSupport>,
+
+    /// Ordered dithering when RGB palettes are reduced to 256/16 colors
+    #[arg(long, global = true)]
+    pub dither: bool,
+
+    /// Force an inline image protocol for the pixel render mode: kitty, sixel or iterm2
+    #[arg(long, value_name = "PROTOCOL")]
+    pub graphics: Option<GraphicsProtocol>,
+
+    /// Disable the pixel render mode even if the terminal looks capable
+    #[arg(long, conflicts_with = "graphics")]
+    pub no_graphics: bool,
+
+    /// Read defaults and key bindings from this file instead of the user config
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Start from this session file; Ctrl+S/Ctrl+O save and load it
+    #[arg(long, value_name = "PATH")]
+    pub session: Option<PathBuf>,
+
+    /// Save the session on quit and restore it on the next start
+    #[arg(long)]
+    pub autosave: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Render one image without opening the TUI
+    Render(RenderArgs),
+    /// Render a zoom sequence as numbered PNG frames
+    Zoom(ZoomArgs),
+}
+
+/// Fractal and view shared by the TUI and the headless subcommands.
+#[derive(clap::Args, Debug)]
+pub struct ViewArgs {
+    /// Start from a Kalles Fraktaler (.kfr) or XaoS (.xpf) location file; the options below override it
+    #[arg(long, value_name = "PATH")]
+    pub location: Option<PathBuf>,
+
+    /// Fractal: mandelbrot, burning-ship or julia
+    #[arg(long = "type", value_name = "TYPE")]
+    pub fractal_type: Option<FractalType>,
+
+    /// View center as X,Y
+    #[arg(long, value_name = "X,Y", value_parser = parse_point, allow_hyphen_values = true)]
+    pub center: Option<(f64, f64)>,
+
+    /// Height of the view in world units
+    #[arg(long)]
+    pub scale: Option<f64>,
+
+    /// Iteration limit
+    #[arg(long)]
+    pub iters: Option<u32>,
+
+    /// Julia constant, real part
+    #[arg(long, allow_hyphen_values = true)]
+    pub cx: Option<f64>,
+
+    /// Julia constant, imaginary part
+    #[arg(long, allow_hyphen_values = true)]
+    pub cy: Option<f64>,
+
+    /// classic, rainbow or magma
+    #[arg(long)]
+    pub palette: Option<Palette>,
+
+    /// NxN samples per pixel (1 disables supersampling)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=8))]
+    pub samples: u32,
+}
+
+impl ViewArgs {
+    /// Replaces the defaults with the options that were given.
+    pub fn apply_defaults(&self, defaults: &mut Defaults) {
+        if let Some(fractal_type) = self.fractal_type {
+            defaults.fractal_type = fractal_type;
+        }
+        if let Some(palette) = self.palette {
+            defaults.palette = palette;
+        }
+        if let Some((x, y)) = self.center {
+            defaults.center_x = x;
+            defaults.center_y = y;
+        }
+        if let Some(scale) = self.scale {
+            defaults.scale = scale;
+        }
+        if let Some(iters) = self.iters {
+            defaults.max_iters = iters;
+        }
+        if let Some(cx) = self.cx {
+            defaults.julia_cx = cx;
+        }
+        if let Some(cy) = self.cy {
+            defaults.julia_cy = cy;
+        }
+    }
+
+    /// Reads `--location` into the defaults, returning what it could not carry over.
+    pub fn import_location(&self, defaults: &mut Defaults) -> io::Result<Vec<String>> {
+        let Some(path) = &self.location else {
+            return Ok(Vec::new());
+        };
+        let mut location = Location::of(&FractalPane::new(0, defaults));
+        let warnings = interop::import(path, &mut location)?;
+        defaults.fractal_type = location.fractal_type;
+        defaults.palette = location.palette;
+        defaults.center_x = location.center_x;
+        defaults.center_y = location.center_y;
+        defaults.scale = location.scale;
+        defaults.max_iters = location.max_iters;
+        defaults.julia_cx = location.julia_cx;
+        defaults.julia_cy = location.julia_cy;
+        Ok(warnings)
+    }
+
+    /// A default pane with the given options applied.
+    pub fn pane(&self, defaults: &Defaults) -> FractalPane {
+        let mut defaults = *defaults;
+        self.apply_defaults(&mut defaults);
+        let mut pane = FractalPane::new(0, &defaults);
+        pane.samples = self.samples;
+        pane
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RenderArgs {
+    #[command(flatten)]
+    pub view: ViewArgs,
+
+    /// Pixels for png/ppm, cells for ansi/txt (default 1920x1080, or the terminal size)
+    #[arg(long, value_name = "WxH", value_parser = parse_size_arg)]
+    pub size: Option<(u32, u32)>,
+
+    /// Glyphs used for ansi and txt output
+    #[arg(long, value_name = "MODE", default_value = "braille")]
+    pub mode: RenderMode,
+
+    /// png, ppm, ansi or txt (default: from the output extension, ansi for stdout)
+    #[arg(long)]
+    pub format: Option<ImageFormat>,
+
+    /// Output file, or - for stdout; stdout when omitted
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ZoomArgs {
+    /// Target view of the zoom
+    #[command(flatten)]
+    pub view: ViewArgs,
+
+    /// Center of the first frame as X,Y
+    #[arg(long, value_name = "X,Y", value_parser = parse_point, allow_hyphen_values = true, default_value = "-0.75,0")]
+    pub from: (f64, f64),
+
+    /// View height of the first frame
+    #[arg(long, default_value_t = 3.0)]
+    pub from_scale: f64,
+
+    /// Number of frames, including the first and last view
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: u32,
+
+    /// Frame size in pixels
+    #[arg(long, value_name = "WxH", value_parser = parse_size_arg, default_value = "640x360")]
+    pub size: (u32, u32),
+
+    /// Also write the frames as an animated zoom.gif
+    #[arg(long)]
+    pub gif: bool,
+
+    /// Directory for frame-NNNNN.png files
+    #[arg(short, long, value_name = "DIR", default_value = "frames")]
+    pub out_dir: PathBuf,
+}
+
+fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    let (x, y) = s.split_once(',').ok_or_else(|| format!("expected X,Y but got '{}'", s))?;
+    let parse = |v: &str| v.trim().parse::<f64>().map_err(|e| format!("'{}': {}", v, e));
+    Ok((parse(x)?, parse(y)?))
+}
+
+fn parse_size_arg(s: &str) -> Result<(u32, u32), String> {
+    parse_size(s).ok_or_else(|| format!("expected WIDTHxHEIGHT but got '{}'", s))
+}
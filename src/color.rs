@@ -1,12 +1,40 @@
 use ratatui::style::Color;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Palette {
     Classic,
     Rainbow,
     Magma,
 }
 
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "classic" => Ok(Palette::Classic),
+            "rainbow" => Ok(Palette::Rainbow),
+            "magma" => Ok(Palette::Magma),
+            other => Err(format!("unknown palette '{}' (expected classic, rainbow or magma)", other)),
+        }
+    }
+}
+
+/// The name `from_str` reads back.
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Palette::Classic => "classic",
+            Palette::Rainbow => "rainbow",
+            Palette::Magma => "magma",
+        })
+    }
+}
+
 pub fn from_hex(hex: u32) -> Color {
     Color::Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
 }
@@ -42,4 +70,317 @@ pub fn get_palette_colors(palette: Palette) -> Vec<Color> {
             from_hex(0xFDDC9E), from_hex(0xFCF2B0), from_hex(0xFCFDBF),
         ],
     }
-}
\ No newline at end of file
+}
+
+/// Truecolor versions of the palettes; Classic and Rainbow trade their ANSI
+/// names for fixed RGB values so they look the same on every terminal theme.
+pub fn get_palette_rgb(palette: Palette) -> Vec<Color> {
+    match palette {
+        Palette::Classic => vec![
+            from_hex(0xE0362C), from_hex(0xFF6B5E), from_hex(0xE5B80B),
+            from_hex(0xFFE66D), from_hex(0x3FAE49), from_hex(0x8BE08B),
+            from_hex(0x2F5BD3), from_hex(0x2EC4D6),
+        ],
+        Palette::Rainbow => vec![
+            from_hex(0xB83DBA), from_hex(0xE87FE9), from_hex(0x3050E0),
+            from_hex(0x6FA8FF), from_hex(0x2EC4D6), from_hex(0x3FAE49),
+            from_hex(0xE5B80B), from_hex(0xFF6B5E),
+        ],
+        Palette::Magma => get_palette_colors(Palette::Magma),
+    }
+}
+
+/// Palette that samples are blended in before quantization: the ANSI palettes
+/// keep their names below truecolor so blends snap back onto the same colors.
+pub fn get_blend_palette(palette: Palette, support: ColorSupport) -> Vec<Color> {
+    match palette {
+        Palette::Classic | Palette::Rainbow if support != ColorSupport::TrueColor => get_palette_colors(palette),
+        _ => get_palette_rgb(palette),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl FromStr for ColorSupport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "truecolor" | "24bit" | "rgb" => Ok(ColorSupport::TrueColor),
+            "256" | "ansi256" => Ok(ColorSupport::Ansi256),
+            "16" | "ansi16" | "ansi" => Ok(ColorSupport::Ansi16),
+            other => Err(format!("unknown color mode '{}' (expected truecolor, 256 or 16)", other)),
+        }
+    }
+}
+
+impl ColorSupport {
+    /// Guesses what the terminal can display from FRAC_TUI_COLORS, COLORTERM and TERM, in that order.
+    pub fn detect() -> Self {
+        if let Some(forced) = env::var("FRAC_TUI_COLORS").ok().and_then(|v| v.parse().ok()) {
+            return forced;
+        }
+
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_ascii_lowercase();
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+            return ColorSupport::TrueColor;
+        }
+
+        let term = env::var("TERM").unwrap_or_default().to_ascii_lowercase();
+        if term.contains("direct") || term == "xterm-kitty" || term == "xterm-ghostty" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorCaps {
+    pub support: ColorSupport,
+    pub dither: bool,
+}
+
+const ANSI16: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::Red, [205, 0, 0]),
+    (Color::Green, [0, 205, 0]),
+    (Color::Yellow, [205, 205, 0]),
+    (Color::Blue, [0, 0, 238]),
+    (Color::Magenta, [205, 0, 205]),
+    (Color::Cyan, [0, 205, 205]),
+    (Color::Gray, [229, 229, 229]),
+    (Color::DarkGray, [127, 127, 127]),
+    (Color::LightRed, [255, 0, 0]),
+    (Color::LightGreen, [0, 255, 0]),
+    (Color::LightYellow, [255, 255, 0]),
+    (Color::LightBlue, [92, 92, 255]),
+    (Color::LightMagenta, [255, 0, 255]),
+    (Color::LightCyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Approximate RGB value of any ratatui color (xterm defaults for the ANSI names).
+pub fn to_rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        Color::Indexed(i) if i < 16 => ANSI16[i as usize].1,
+        Color::Indexed(i) if i < 232 => {
+            let i = i - 16;
+            [CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize]]
+        }
+        Color::Indexed(i) => [8 + 10 * (i - 232); 3],
+        named => ANSI16.iter().find(|(c, _)| *c == named).map(|(_, rgb)| *rgb).unwrap_or([0, 0, 0]),
+    }
+}
+
+/// SGR parameters selecting `color` as foreground or background, e.g. `38;2;255;0;0`.
+pub fn sgr(color: Color, foreground: bool) -> String {
+    let base = if foreground { 30 } else { 40 };
+    match color {
+        Color::Reset => format!("{}", base + 9),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        Color::Indexed(i) => format!("{};5;{}", base + 8, i),
+        named => match ANSI16.iter().position(|(c, _)| *c == named) {
+            Some(i) if i < 8 => format!("{}", base + i),
+            Some(i) => format!("{}", base + 60 + i - 8),
+            None => format!("{}", base + 9),
+        },
+    }
+}
+
+pub fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b.iter()).map(|(&x, &y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+fn nearest_cube_level(v: u8) -> usize {
+    CUBE_LEVELS.iter().enumerate()
+        .min_by_key(|(_, &level)| (level as i32 - v as i32).abs())
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn quantize_256(rgb: [u8; 3]) -> Color {
+    let [r, g, b] = rgb.map(nearest_cube_level);
+    let cube = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+
+    let avg = (rgb.iter().map(|&v| v as u32).sum::<u32>() / 3) as u8;
+    let gray_step = (avg.saturating_sub(3) / 10).min(23);
+    let gray_level = 8 + 10 * gray_step;
+
+    if distance(rgb, [gray_level; 3]) < distance(rgb, cube) {
+        Color::Indexed(232 + gray_step)
+    } else {
+        Color::Indexed(16 + 36 * r as u8 + 6 * g as u8 + b as u8)
+    }
+}
+
+fn quantize_16(rgb: [u8; 3]) -> Color {
+    ANSI16.iter()
+        .min_by_key(|(_, candidate)| distance(rgb, *candidate))
+        .map(|(c, _)| *c)
+        .unwrap_or(Color::White)
+}
+
+/// Maps a color onto what the terminal supports. With `cell` set, a 4x4 Bayer
+/// threshold is added first so flat gradients break up into an ordered pattern.
+pub fn quantize(color: Color, support: ColorSupport, cell: Option<(u32, u32)>) -> Color {
+    let rgb = match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        other => return other,
+    };
+
+    let (spread, quantizer): (f32, fn([u8; 3]) -> Color) = match support {
+        ColorSupport::TrueColor => return color,
+        ColorSupport::Ansi256 => (40.0, quantize_256),
+        ColorSupport::Ansi16 => (128.0, quantize_16),
+    };
+
+    let rgb = match cell {
+        Some((x, y)) => {
+            let threshold = BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 / 16.0 - 0.5;
+            rgb.map(|v| (v as f32 + threshold * spread).clamp(0.0, 255.0) as u8)
+        }
+        None => rgb,
+    };
+    quantizer(rgb)
+}
+
+/// A palette resolved against the terminal's capabilities. When dithering,
+/// every palette entry gets one variant per Bayer cell, looked up by slot.
+pub struct ResolvedPalette {
+    colors: Vec<Color>,
+    variants: usize,
+}
+
+impl ResolvedPalette {
+    pub fn new(palette: Palette, caps: ColorCaps) -> Self {
+        let ansi_native = matches!(palette, Palette::Classic | Palette::Rainbow);
+
+        if caps.support == ColorSupport::TrueColor {
+            return Self { colors: get_palette_rgb(palette), variants: 1 };
+        }
+        if ansi_native {
+            return Self { colors: get_palette_colors(palette), variants: 1 };
+        }
+
+        let source = get_palette_rgb(palette);
+        if !caps.dither {
+            let colors = source.iter().map(|&c| quantize(c, caps.support, None)).collect();
+            return Self { colors, variants: 1 };
+        }
+
+        let colors = source.iter()
+            .flat_map(|&c| (0..16).map(move |cell| quantize(c, caps.support, Some((cell % 4, cell / 4)))))
+            .collect();
+        Self { colors, variants: 16 }
+    }
+
+    pub fn entries(&self) -> usize {
+        self.colors.len() / self.variants
+    }
+
+    pub fn slot(&self, index: usize, x: u32, y: u32) -> usize {
+        if self.variants == 1 {
+            index
+        } else {
+            index * self.variants + ((y % 4) * 4 + x % 4) as usize
+        }
+    }
+
+    pub fn color(&self, slot: usize) -> Color {
+        self.colors[slot]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Transfer {
+    Linear,
+    Sqrt,
+    Log,
+}
+
+/// How escape iterations are turned into a palette index.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorMapping {
+    pub offset: usize,
+    pub density: f64,
+    pub reverse: bool,
+    pub transfer: Transfer,
+}
+
+impl Default for ColorMapping {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            density: 1.0,
+            reverse: false,
+            transfer: Transfer::Linear,
+        }
+    }
+}
+
+impl ColorMapping {
+    pub fn index(&self, iterations: u32, max_iters: u32, palette_len: usize) -> usize {
+        let it = iterations as f64;
+        let max = max_iters.max(1) as f64;
+
+        // Sqrt and log are scaled so that max_iters maps onto itself, which keeps
+        // the density meaning "iterations per color step" across transfers.
+        let t = match self.transfer {
+            Transfer::Linear => it,
+            Transfer::Sqrt => (it * max).sqrt(),
+            Transfer::Log => max * (1.0 + it).ln() / (1.0 + max).ln(),
+        };
+
+        let step = (t / self.density.max(0.01)) as usize;
+        let index = (step + self.offset) % palette_len;
+        if self.reverse {
+            palette_len - 1 - index
+        } else {
+            index
+        }
+    }
+
+    pub fn rotate(&mut self, forward: bool, palette_len: usize) {
+        self.offset = if forward {
+            (self.offset + 1) % palette_len
+        } else {
+            (self.offset + palette_len - 1) % palette_len
+        };
+    }
+
+    pub fn toggle_transfer(&mut self) {
+        self.transfer = match self.transfer {
+            Transfer::Linear => Transfer::Sqrt,
+            Transfer::Sqrt => Transfer::Log,
+            Transfer::Log => Transfer::Linear,
+        };
+    }
+
+    pub fn label(&self) -> String {
+        let transfer = match self.transfer {
+            Transfer::Linear => "lin",
+            Transfer::Sqrt => "sqrt",
+            Transfer::Log => "log",
+        };
+        let reverse = if self.reverse { " rev" } else { "" };
+        format!("+{} x{:.2}{} {}", self.offset, self.density, reverse, transfer)
+    }
+}
@@ -0,0 +1,527 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Deserializer};
+
+use crate::color::Palette;
+use crate::hooks::FractalType;
+
+/// Starting view and navigation steps for new panes and `r`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    #[serde(deserialize_with = "from_name")]
+    pub fractal_type: FractalType,
+    #[serde(deserialize_with = "from_name")]
+    pub palette: Palette,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub scale: f64,
+    pub max_iters: u32,
+    pub julia_cx: f64,
+    pub julia_cy: f64,
+    /// Scale multiplier of one zoom-in step; zooming out divides by it.
+    pub zoom_factor: f64,
+    /// Pan distance as a fraction of the view height.
+    pub pan_step: f64,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            fractal_type: FractalType::Mandelbrot,
+            palette: Palette::Classic,
+            center_x: -0.75,
+            center_y: 0.0,
+            scale: 3.0,
+            max_iters: 1100,
+            julia_cx: -0.5125,
+            julia_cy: 0.5213,
+            zoom_factor: 0.9,
+            pan_step: 0.1,
+        }
+    }
+}
+
+impl Defaults {
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = [self.center_x, self.center_y, self.julia_cx, self.julia_cy];
+        if finite.iter().any(|v| !v.is_finite()) {
+            return Err("center and julia values must be finite numbers".to_string());
+        }
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(format!("scale must be positive, got {}", self.scale));
+        }
+        if self.max_iters < 10 {
+            return Err(format!("max_iters must be at least 10, got {}", self.max_iters));
+        }
+        if !(self.zoom_factor > 0.0 && self.zoom_factor < 1.0) {
+            return Err(format!("zoom_factor must be between 0 and 1, got {}", self.zoom_factor));
+        }
+        if !(self.pan_step.is_finite() && self.pan_step > 0.0) {
+            return Err(format!("pan_step must be positive, got {}", self.pan_step));
+        }
+        Ok(())
+    }
+}
+
+/// Takes the same names as the command line, e.g. `burning-ship` or `magma`.
+fn from_name<'de, D: Deserializer<'de>, T: FromStr<Err = String>>(d: D) -> Result<T, D::Error> {
+    String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    NextPalette,
+    RotatePaletteBack,
+    RotatePaletteForward,
+    DensityDown,
+    DensityUp,
+    ReversePalette,
+    CycleTransfer,
+    CycleFractal,
+    CycleRenderMode,
+    CellColor,
+    Supersampling,
+    Jitter,
+    FillStrategy,
+    MoreIters,
+    FewerIters,
+    AutoIters,
+    InteriorPeriod,
+    ExportPng,
+    ExportZoom,
+    DumpAnsi,
+    DumpText,
+    Undo,
+    Redo,
+    History,
+    CopyLocation,
+    PasteLocation,
+    ImportLocation,
+    ExportLocation,
+    Bookmark,
+    Bookmarks,
+    Reset,
+    SplitDown,
+    SplitUp,
+    SplitRight,
+    SplitLeft,
+    ClosePane,
+    PaneWider,
+    PaneNarrower,
+    PaneTaller,
+    PaneShorter,
+    EqualizePanes,
+    CycleFocus,
+    FocusLeft,
+    FocusDown,
+    FocusUp,
+    FocusRight,
+    SwapLeft,
+    SwapDown,
+    SwapUp,
+    SwapRight,
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveRight,
+    RotateSplit,
+    FlipSplit,
+    FocusPane(usize),
+    Record,
+    SaveSession,
+    LoadSession,
+    Help,
+    Quit,
+}
+
+/// Every action with its config name, default keys and help text, in help order.
+const ACTIONS: &[(&str, Action, &[&str], &str)] = &[
+    ("pan_left", Action::PanLeft, &["Left"], "Pan Left"),
+    ("pan_right", Action::PanRight, &["Right"], "Pan Right"),
+    ("pan_up", Action::PanUp, &["Up"], "Pan Up"),
+    ("pan_down", Action::PanDown, &["Down"], "Pan Down"),
+    ("zoom_in", Action::ZoomIn, &["+", "="], "Zoom In (center)"),
+    ("zoom_out", Action::ZoomOut, &["-", "_"], "Zoom Out (center)"),
+    ("next_palette", Action::NextPalette, &["Space"], "Cycle Palette"),
+    ("rotate_palette_back", Action::RotatePaletteBack, &[","], "Rotate Palette Offset Back"),
+    ("rotate_palette_forward", Action::RotatePaletteForward, &["."], "Rotate Palette Offset Forward"),
+    ("density_down", Action::DensityDown, &["["], "Decrease Color Density"),
+    ("density_up", Action::DensityUp, &["]"], "Increase Color Density"),
+    ("reverse_palette", Action::ReversePalette, &["v"], "Reverse Palette Direction"),
+    ("cycle_transfer", Action::CycleTransfer, &["t"], "Cycle Transfer (lin/sqrt/log)"),
+    ("cycle_fractal", Action::CycleFractal, &["b"], "Cycle Fractal Type"),
+    ("cycle_render_mode", Action::CycleRenderMode, &["m"], "Cycle Render Mode (incl. Pixels)"),
+    ("cell_color", Action::CellColor, &["c"], "Braille Cell Color (majority/average)"),
+    ("supersampling", Action::Supersampling, &["a"], "Cycle Supersampling (1x1..4x4)"),
+    ("jitter", Action::Jitter, &["j"], "Toggle Jittered Samples"),
+    ("fill_strategy", Action::FillStrategy, &["f"], "Toggle Mariani-Silver Fill"),
+    ("more_iters", Action::MoreIters, &["d"], "Increase Iterations"),
+    ("fewer_iters", Action::FewerIters, &["s"], "Decrease Iterations"),
+    ("auto_iters", Action::AutoIters, &["i"], "Toggle Auto Iterations"),
+    ("interior_period", Action::InteriorPeriod, &["p"], "Color Interior by Cycle Period"),
+    ("export_png", Action::ExportPng, &["e"], "Export Pane to PNG (WxH)"),
+    ("export_zoom", Action::ExportZoom, &["z"], "Export Zoom Frames (N WxH [gif])"),
+    ("dump_ansi", Action::DumpAnsi, &["w"], "Dump Pane as ANSI Text"),
+    ("dump_text", Action::DumpText, &["W"], "Dump Pane as Plain Text"),
+    ("undo", Action::Undo, &["u"], "Undo View Change"),
+    ("redo", Action::Redo, &["y"], "Redo View Change"),
+    ("history", Action::History, &["H"], "View History (Back/Forward)"),
+    ("copy_location", Action::CopyLocation, &["Y"], "Copy Location String (OSC 52)"),
+    ("paste_location", Action::PasteLocation, &["g"], "Go to Location String (paste)"),
+    ("import_location", Action::ImportLocation, &["l"], "Import Location File (.kfr/.xpf)"),
+    ("export_location", Action::ExportLocation, &["x"], "Export Location File (.kfr/.xpf)"),
+    ("bookmark", Action::Bookmark, &["k"], "Save Bookmark"),
+    ("bookmarks", Action::Bookmarks, &["K"], "Browse Bookmarks"),
+    ("reset", Action::Reset, &["r"], "Reset View"),
+    ("split_down", Action::SplitDown, &["D"], "Split Pane Down"),
+    ("split_up", Action::SplitUp, &["U"], "Split Pane Up"),
+    ("split_right", Action::SplitRight, &["R"], "Split Pane Right"),
+    ("split_left", Action::SplitLeft, &["L"], "Split Pane Left"),
+    ("close_pane", Action::ClosePane, &["X"], "Close Active Pane"),
+    ("pane_wider", Action::PaneWider, &["Shift+Right"], "Widen Active Pane"),
+    ("pane_narrower", Action::PaneNarrower, &["Shift+Left"], "Narrow Active Pane"),
+    ("pane_taller", Action::PaneTaller, &["Shift+Up"], "Heighten Active Pane"),
+    ("pane_shorter", Action::PaneShorter, &["Shift+Down"], "Shorten Active Pane"),
+    ("equalize_panes", Action::EqualizePanes, &["E"], "Equalize Pane Sizes"),
+    ("cycle_focus", Action::CycleFocus, &["Tab"], "Cycle Focus"),
+    ("focus_left", Action::FocusLeft, &["Alt+Left", "Alt+h"], "Focus Pane to the Left"),
+    ("focus_down", Action::FocusDown, &["Alt+Down", "Alt+j"], "Focus Pane Below"),
+    ("focus_up", Action::FocusUp, &["Alt+Up", "Alt+k"], "Focus Pane Above"),
+    ("focus_right", Action::FocusRight, &["Alt+Right", "Alt+l"], "Focus Pane to the Right"),
+    ("swap_left", Action::SwapLeft, &["Alt+Shift+Left", "Alt+H"], "Swap with Pane to the Left"),
+    ("swap_down", Action::SwapDown, &["Alt+Shift+Down", "Alt+J"], "Swap with Pane Below"),
+    ("swap_up", Action::SwapUp, &["Alt+Shift+Up", "Alt+K"], "Swap with Pane Above"),
+    ("swap_right", Action::SwapRight, &["Alt+Shift+Right", "Alt+L"], "Swap with Pane to the Right"),
+    ("move_left", Action::MoveLeft, &["Ctrl+Shift+Left"], "Move Pane Past Its Left Neighbor"),
+    ("move_down", Action::MoveDown, &["Ctrl+Shift+Down"], "Move Pane Past the Pane Below"),
+    ("move_up", Action::MoveUp, &["Ctrl+Shift+Up"], "Move Pane Past the Pane Above"),
+    ("move_right", Action::MoveRight, &["Ctrl+Shift+Right"], "Move Pane Past Its Right Neighbor"),
+    ("rotate_split", Action::RotateSplit, &["o"], "Rotate Panes of the Active Split"),
+    ("flip_split", Action::FlipSplit, &["O"], "Flip Active Split (side by side/stacked)"),
+    ("focus_1", Action::FocusPane(1), &["1"], "Switch Focus to Pane 1"),
+    ("focus_2", Action::FocusPane(2), &["2"], "Switch Focus to Pane 2"),
+    ("focus_3", Action::FocusPane(3), &["3"], "Switch Focus to Pane 3"),
+    ("focus_4", Action::FocusPane(4), &["4"], "Switch Focus to Pane 4"),
+    ("focus_5", Action::FocusPane(5), &["5"], "Switch Focus to Pane 5"),
+    ("focus_6", Action::FocusPane(6), &["6"], "Switch Focus to Pane 6"),
+    ("focus_7", Action::FocusPane(7), &["7"], "Switch Focus to Pane 7"),
+    ("focus_8", Action::FocusPane(8), &["8"], "Switch Focus to Pane 8"),
+    ("focus_9", Action::FocusPane(9), &["9"], "Switch Focus to Pane 9"),
+    ("record", Action::Record, &["Ctrl+r"], "Start/Stop asciicast Recording"),
+    ("save_session", Action::SaveSession, &["Ctrl+s"], "Save Session"),
+    ("load_session", Action::LoadSession, &["Ctrl+o"], "Load Session"),
+    ("help", Action::Help, &["h"], "Toggle Help"),
+    ("quit", Action::Quit, &["q", "Esc"], "Quit"),
+];
+
+impl Action {
+    /// All actions in help order, with their descriptions.
+    pub fn all() -> impl Iterator<Item = (Action, &'static str)> {
+        ACTIONS.iter().map(|&(_, action, _, description)| (action, description))
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ACTIONS.iter()
+            .find(|(name, ..)| *name == s)
+            .map(|&(_, action, ..)| action)
+            .ok_or_else(|| format!("unknown action '{}'", s))
+    }
+}
+
+/// A key with its modifiers. Shift is part of the character for printable keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn matches(&self, key: &KeyEvent) -> bool {
+        let mut modifiers = key.modifiers;
+        if let KeyCode::Char(_) = key.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        self.code == key.code && self.modifiers == modifiers
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    /// Parses `Ctrl+r`, `Shift+Left`, `Space`, `+`, `Ctrl++` and the like.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, key) = match s.strip_suffix("++") {
+            Some(prefix) => (prefix, "+"),
+            None => match s.rsplit_once('+') {
+                Some((prefix, key)) if !key.is_empty() => (prefix, key),
+                _ => ("", s),
+            },
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in prefix.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in key '{}'", name, s)),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "enter" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "space" => KeyCode::Char(' '),
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{}'", s)),
+                },
+            },
+        };
+
+        // Terminals report Shift+a as 'A', so that is what the binding has to match.
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                Ok(Self { code: KeyCode::Char(c.to_ascii_uppercase()), modifiers })
+            }
+            _ => Ok(Self { code, modifiers }),
+        }
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl"),
+            (KeyModifiers::ALT, "Alt"),
+            (KeyModifiers::SHIFT, "Shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{} + ", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = ACTIONS.iter()
+            .flat_map(|&(_, action, keys, _)| {
+                keys.iter().map(move |key| (key.parse().expect("built-in key binding"), action))
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings.iter().find(|(binding, _)| binding.matches(key)).map(|&(_, action)| action)
+    }
+
+    /// Keys bound to `action`, joined for display, e.g. `q / Esc`.
+    pub fn label(&self, action: Action) -> String {
+        let keys: Vec<String> = self.bindings.iter()
+            .filter(|&&(_, bound)| bound == action)
+            .map(|(binding, _)| binding.to_string())
+            .collect();
+        keys.join(" / ")
+    }
+
+    /// Replaces the keys of each listed action; a key taken from an
+    /// action that is not listed is removed from that action's defaults.
+    fn remap(&mut self, keys: BTreeMap<String, Keys>) -> Result<(), String> {
+        let mut remapped = Vec::new();
+        let mut custom: Vec<(KeyBinding, Action)> = Vec::new();
+        for (name, keys) in keys {
+            let action: Action = name.parse()?;
+            remapped.push(action);
+            let keys = match keys {
+                Keys::One(key) => vec![key],
+                Keys::Many(keys) => keys,
+            };
+            for key in keys {
+                let binding: KeyBinding = key.parse()?;
+                if let Some(&(_, other)) = custom.iter().find(|(bound, _)| *bound == binding) {
+                    return Err(format!("key '{}' is bound to both {} and {}", key, action_name(other), name));
+                }
+                custom.push((binding, action));
+            }
+        }
+
+        self.bindings.retain(|(binding, action)| {
+            !remapped.contains(action) && !custom.iter().any(|(bound, _)| bound == binding)
+        });
+        self.bindings.extend(custom);
+        Ok(())
+    }
+}
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS.iter().find(|&&(_, a, ..)| a == action).map_or("?", |&(name, ..)| name)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    defaults: Defaults,
+    keys: BTreeMap<String, Keys>,
+}
+
+#[derive(Default)]
+pub struct Config {
+    pub defaults: Defaults,
+    pub keymap: Keymap,
+}
+
+/// `config.toml` in the user config directory (`$XDG_CONFIG_HOME/frac-tui-rs` on Linux).
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("frac-tui-rs").join("config.toml"))
+}
+
+/// Reads and checks a config file; a missing default file means the built-in settings.
+pub fn load(path: Option<&Path>) -> io::Result<Config> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => return Ok(Config::default()),
+        Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+    };
+
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+    let file: ConfigFile = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    file.defaults.validate().map_err(invalid)?;
+    let mut keymap = Keymap::default();
+    keymap.remap(file.keys).map_err(invalid)?;
+    Ok(Config { defaults: file.defaults, keymap })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn keys(entries: &[(&str, &[&str])]) -> BTreeMap<String, Keys> {
+        entries.iter()
+            .map(|&(name, keys)| (name.to_string(), Keys::Many(keys.iter().map(|key| key.to_string()).collect())))
+            .collect()
+    }
+
+    #[test]
+    fn plus_is_a_key_of_its_own() {
+        let plus = KeyBinding { code: KeyCode::Char('+'), modifiers: KeyModifiers::NONE };
+        assert_eq!("+".parse(), Ok(plus));
+        assert_eq!("Ctrl++".parse(), Ok(KeyBinding { modifiers: KeyModifiers::CONTROL, ..plus }));
+    }
+
+    #[test]
+    fn modifiers_stack_in_any_case() {
+        let expected = KeyBinding { code: KeyCode::Left, modifiers: KeyModifiers::ALT | KeyModifiers::SHIFT };
+        assert_eq!("Alt+Shift+Left".parse(), Ok(expected));
+        assert_eq!("shift+alt+left".parse(), Ok(expected));
+    }
+
+    #[test]
+    fn shift_folds_into_printable_keys() {
+        let binding: KeyBinding = "Shift+k".parse().unwrap();
+        assert_eq!(binding, KeyBinding { code: KeyCode::Char('K'), modifiers: KeyModifiers::NONE });
+        assert!(binding.matches(&press(KeyCode::Char('K'), KeyModifiers::SHIFT)));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        assert!("Hyper+x".parse::<KeyBinding>().is_err());
+        assert!("Banana".parse::<KeyBinding>().is_err());
+        assert!("F13".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn remap_replaces_the_listed_actions_only() {
+        let mut keymap = Keymap::default();
+        keymap.remap(keys(&[("undo", &["z", "Ctrl+z"])])).unwrap();
+        assert_eq!(keymap.action(&press(KeyCode::Char('z'), KeyModifiers::NONE)), Some(Action::Undo));
+        assert_eq!(keymap.action(&press(KeyCode::Char('z'), KeyModifiers::CONTROL)), Some(Action::Undo));
+        assert_eq!(keymap.action(&press(KeyCode::Char('u'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.action(&press(KeyCode::Char('y'), KeyModifiers::NONE)), Some(Action::Redo));
+    }
+
+    #[test]
+    fn remap_takes_keys_from_unlisted_actions() {
+        let mut keymap = Keymap::default();
+        keymap.remap(keys(&[("undo", &["q"])])).unwrap();
+        assert_eq!(keymap.action(&press(KeyCode::Char('q'), KeyModifiers::NONE)), Some(Action::Undo));
+        assert_eq!(keymap.action(&press(KeyCode::Esc, KeyModifiers::NONE)), Some(Action::Quit));
+        assert_eq!(keymap.label(Action::Quit), "Esc");
+    }
+
+    #[test]
+    fn remap_refuses_a_key_bound_twice() {
+        let mut keymap = Keymap::default();
+        let error = keymap.remap(keys(&[("undo", &["z"]), ("redo", &["z"])])).unwrap_err();
+        assert_eq!(error, "key 'z' is bound to both redo and undo");
+        assert!(keymap.remap(keys(&[("teleport", &["t"])])).is_err());
+    }
+}
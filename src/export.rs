@@ -0,0 +1,243 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crossterm::terminal;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::widgets::{Block, StatefulWidget};
+
+use crate::cli::RenderArgs;
+use crate::color::{sgr, ColorCaps};
+use crate::config::Defaults;
+use crate::graphics::encode_png;
+use crate::hooks::FractalPane;
+use crate::render::{render_rgb, FractalWidget, Viewport};
+
+/// Rows rendered per band; progress is reported after each band.
+const BAND_ROWS: u32 = 64;
+
+/// Largest side accepted for an offscreen render.
+pub const MAX_EXPORT_SIDE: u32 = 16384;
+
+/// Output formats of the headless renderer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Ansi,
+    /// Glyphs without colors.
+    Text,
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "ppm" => Ok(ImageFormat::Ppm),
+            "ansi" | "ans" => Ok(ImageFormat::Ansi),
+            "txt" | "text" => Ok(ImageFormat::Text),
+            other => Err(format!("unknown format '{}' (expected png, ppm, ansi or txt)", other)),
+        }
+    }
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+/// Text formats for dumping a pane exactly as it is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextDump {
+    /// Glyphs with SGR color codes, written as `.ans`.
+    Ansi,
+    /// Glyphs only, written as `.txt`.
+    Plain,
+}
+
+pub fn timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Parses sizes written as `WIDTHxHEIGHT`, e.g. `3840x2160`.
+pub fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.trim().split_once(['x', 'X'])?;
+    let (w, h) = (w.trim().parse().ok()?, h.trim().parse().ok()?);
+    let valid = |side: u32| (1..=MAX_EXPORT_SIDE).contains(&side);
+    (valid(w) && valid(h)).then_some((w, h))
+}
+
+/// Renders the pane's view as a `width`x`height` RGB image with square pixels,
+/// band by band so that `rows_done` can be watched from another thread.
+pub fn render_image(pane: &FractalPane, width: u32, height: u32, rows_done: &AtomicU32) -> Vec<u8> {
+    let view = Viewport::square(pane.center_x, pane.center_y, pane.scale, width, height);
+    let row_height = (view.top - view.bottom) / height as f64;
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+
+    for start in (0..height).step_by(BAND_ROWS as usize) {
+        let rows = BAND_ROWS.min(height - start);
+        let band = Viewport {
+            top: view.top - start as f64 * row_height,
+            bottom: view.top - (start + rows) as f64 * row_height,
+            ..view
+        };
+        rgb.extend(render_rgb(band, pane, width, rows).0);
+        rows_done.store(start + rows, Ordering::Relaxed);
+    }
+    rgb
+}
+
+/// An export running on a background thread, counting finished steps
+/// (image rows or animation frames) for its progress display.
+#[derive(Clone)]
+pub struct ExportJob {
+    pub path: PathBuf,
+    steps: u32,
+    done: Arc<AtomicU32>,
+    result: Arc<Mutex<Option<Result<(), String>>>>,
+}
+
+impl ExportJob {
+    /// Runs `work` on a new thread; it reports progress through the counter it is given.
+    pub fn spawn(path: PathBuf, steps: u32, work: impl FnOnce(&Path, &AtomicU32) -> io::Result<()> + Send + 'static) -> Self {
+        let job = ExportJob {
+            path,
+            steps,
+            done: Arc::new(AtomicU32::new(0)),
+            result: Arc::new(Mutex::new(None)),
+        };
+
+        let worker = job.clone();
+        thread::spawn(move || {
+            let written = work(&worker.path, &worker.done);
+            if let Ok(mut result) = worker.result.lock() {
+                *result = Some(written.map_err(|e| e.to_string()));
+            }
+        });
+        job
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.result.lock().map(|r| r.is_none()).unwrap_or(false)
+    }
+
+    pub fn status(&self) -> String {
+        let name = self.path.display();
+        match self.result.lock().as_deref() {
+            Ok(None) => {
+                let done = self.done.load(Ordering::Relaxed);
+                format!("Exporting {} {}%", name, done as u64 * 100 / self.steps.max(1) as u64)
+            }
+            Ok(Some(Ok(()))) => format!("Saved {}", name),
+            Ok(Some(Err(e))) => format!("Export failed: {}", e),
+            Err(_) => "Export failed".to_string(),
+        }
+    }
+}
+
+/// Starts rendering a copy of `pane` to a timestamped PNG in the working directory.
+pub fn spawn_png(pane: &FractalPane, width: u32, height: u32) -> ExportJob {
+    let pane = pane.clone();
+    let path = PathBuf::from(format!("fractal-{}-{}x{}.png", timestamp(), width, height));
+    ExportJob::spawn(path, height, move |path, rows_done| {
+        let rgb = render_image(&pane, width, height, rows_done);
+        fs::write(path, encode_png(&rgb, width, height)?)
+    })
+}
+
+pub fn encode_ppm(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    data.extend_from_slice(rgb);
+    data
+}
+
+/// Serializes a buffer region line by line, with SGR color codes when `color` is set.
+pub fn buffer_to_ansi(buf: &Buffer, area: Rect, color: bool) -> String {
+    let mut out = String::new();
+    for y in area.top()..area.bottom() {
+        let mut style = (Color::Reset, Color::Reset);
+        for x in area.left()..area.right() {
+            let cell = &buf[(x, y)];
+            if color && (cell.fg, cell.bg) != style {
+                style = (cell.fg, cell.bg);
+                out.push_str(&format!("\x1b[0;{};{}m", sgr(cell.fg, true), sgr(cell.bg, false)));
+            }
+            out.push_str(cell.symbol());
+        }
+        if style != (Color::Reset, Color::Reset) {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes a buffer region to a timestamped `.ans` or `.txt` file in the working directory.
+pub fn dump_text(buf: &Buffer, area: Rect, kind: TextDump) -> io::Result<PathBuf> {
+    let (extension, color) = match kind {
+        TextDump::Ansi => ("ans", true),
+        TextDump::Plain => ("txt", false),
+    };
+    let path = PathBuf::from(format!("fractal-{}.{}", timestamp(), extension));
+    fs::write(&path, buffer_to_ansi(buf, area, color))?;
+    Ok(path)
+}
+
+/// Entry point of `frac-tui-rs render`: one image to a file or stdout, no TUI.
+pub fn render_headless(args: &RenderArgs, caps: ColorCaps, defaults: &Defaults) -> io::Result<()> {
+    let mut pane = args.view.pane(defaults);
+    pane.render_mode = args.mode;
+
+    let format = match (args.format, args.output.as_deref()) {
+        (Some(format), _) => format,
+        (None, Some(path)) if path.extension().is_some() => ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: unknown image type (expected .png, .ppm, .ans or .txt, or pass --format)", path.display()),
+            )
+        })?,
+        // Stdout and files without an extension get ANSI text.
+        (None, _) => ImageFormat::Ansi,
+    };
+
+    let data = match format {
+        ImageFormat::Png | ImageFormat::Ppm => {
+            let (width, height) = args.size.unwrap_or((1920, 1080));
+            let rgb = render_image(&pane, width, height, &AtomicU32::new(0));
+            if format == ImageFormat::Png {
+                encode_png(&rgb, width, height)?
+            } else {
+                encode_ppm(&rgb, width, height)
+            }
+        }
+        ImageFormat::Ansi | ImageFormat::Text => {
+            let (cols, rows) = args.size
+                .or_else(|| terminal::size().ok().map(|(c, r)| (c as u32, r as u32)))
+                .unwrap_or((80, 24));
+            let area = Rect::new(0, 0, cols.min(u16::MAX as u32) as u16, rows.min(u16::MAX as u32) as u16);
+            let mut buf = Buffer::empty(area);
+            // Cells are about twice as tall as wide, as in the TUI.
+            let view = Viewport::square(pane.center_x, pane.center_y, pane.scale, area.width as u32, area.height as u32 * 2);
+            FractalWidget { view, pane: &pane, caps, block: Block::default() }
+                .render(area, &mut buf, &mut 0.0);
+            buffer_to_ansi(&buf, area, format == ImageFormat::Ansi).into_bytes()
+        }
+    };
+
+    match &args.output {
+        Some(path) if path.as_os_str() != "-" => {
+            fs::write(path, data).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+        }
+        _ => io::stdout().write_all(&data),
+    }
+}
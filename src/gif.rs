@@ -0,0 +1,220 @@
+//! Minimal animated GIF writer: one local color table per frame (reduced the
+//! same way as sixel output), LZW-compressed, looping forever.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::graphics::index_colors;
+
+/// GIF codes are at most 12 bits wide.
+const MAX_CODES: u16 = 4096;
+
+pub struct GifWriter<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(mut out: W, width: u32, height: u32) -> io::Result<Self> {
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "GIF frames are limited to 65535 pixels per side")),
+        };
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // No global color table, no background color, square pixels.
+        out.write_all(&[0x00, 0x00, 0x00])?;
+        // NETSCAPE2.0 application extension: loop count 0 repeats forever.
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Self { out, width, height })
+    }
+
+    /// Appends an RGB frame shown for `delay` hundredths of a second.
+    pub fn write_frame(&mut self, rgb: &[u8], delay: u16) -> io::Result<()> {
+        let (mut palette, indices) = index_colors(rgb);
+        // The table size field is a power of two, at least 2 entries.
+        let bits = (palette.len().max(2) as f64).log2().ceil() as u8;
+        palette.resize(1 << bits, [0, 0, 0]);
+
+        let [delay_lo, delay_hi] = delay.to_le_bytes();
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00, delay_lo, delay_hi, 0x00, 0x00])?;
+
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0x80 | (bits - 1)])?;
+        for color in &palette {
+            self.out.write_all(color)?;
+        }
+
+        let min_code_size = bits.max(2);
+        self.out.write_all(&[min_code_size])?;
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()
+    }
+}
+
+/// Packs variable-width codes least significant bit first, as GIF expects.
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn push(&mut self, code: u16, width: u8) {
+        self.acc |= (code as u32) << self.count;
+        self.count += width;
+        while self.count >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut bits = BitWriter { bytes: Vec::new(), acc: 0, count: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_code_size + 1;
+
+    bits.push(clear, width);
+    let Some((&first, rest)) = indices.split_first() else {
+        bits.push(end, width);
+        return bits.finish();
+    };
+
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        bits.push(prefix, width);
+        if next < MAX_CODES {
+            table.insert((prefix, index), next);
+            // The decoder widens one code later than the encoder adds the entry.
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        } else {
+            bits.push(clear, width);
+            table.clear();
+            next = end + 1;
+            width = min_code_size + 1;
+        }
+        prefix = index as u16;
+    }
+    bits.push(prefix, width);
+    bits.push(end, width);
+    bits.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plain GIF decoder: widens as soon as the table fills the current width.
+    fn lzw_decode(bytes: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> { (0..clear + 2).map(|i| vec![i as u8]).collect() };
+        let mut table = reset();
+        let mut width = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let (mut acc, mut count, mut pos) = (0u32, 0u8, 0);
+        loop {
+            while count < width {
+                acc |= (bytes[pos] as u32) << count;
+                pos += 1;
+                count += 8;
+            }
+            let code = (acc & ((1 << width) - 1)) as usize;
+            acc >>= width;
+            count -= width;
+
+            if code == clear {
+                table = reset();
+                width = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) if code == table.len() => [&previous[..], &previous[..1]].concat(),
+                _ => panic!("code {} is not in the table of {}", code, table.len()),
+            };
+            out.extend_from_slice(&entry);
+            if let Some(previous) = previous.filter(|_| table.len() < MAX_CODES as usize) {
+                table.push([&previous[..], &entry[..1]].concat());
+                if table.len() == 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+            previous = Some(entry);
+        }
+    }
+
+    fn round_trip(indices: &[u8], min_code_size: u8) {
+        assert_eq!(lzw_decode(&lzw_encode(indices, min_code_size), min_code_size), indices);
+    }
+
+    #[test]
+    fn short_inputs_round_trip() {
+        round_trip(&[], 2);
+        round_trip(&[3], 2);
+        round_trip(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 2);
+        round_trip(&[0, 1, 0, 1, 0, 1, 2, 3, 2, 3, 2, 3], 2);
+    }
+
+    #[test]
+    fn long_runs_widen_to_twelve_bits() {
+        let indices: Vec<u8> = (0..200_000).map(|i| (i / 7 % 4) as u8).collect();
+        round_trip(&indices, 2);
+    }
+
+    #[test]
+    fn noise_fills_the_table_and_clears_it() {
+        // Random pixels add a code for nearly every pixel, so 256 colors run
+        // past 4096 codes many times over.
+        let mut state = 0x2545_f491_u32;
+        let indices: Vec<u8> = (0..100_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        round_trip(&indices, 8);
+        let eight_colors: Vec<u8> = indices.iter().map(|i| i % 8).collect();
+        round_trip(&eight_colors, 3);
+    }
+}
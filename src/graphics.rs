@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crossterm::{cursor::MoveTo, queue, terminal::window_size};
+use ratatui::layout::Rect;
+
+use crate::color::ColorMapping;
+use crate::location::Location;
+
+/// Inline image protocols understood by the pixel render mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Sixel,
+    Iterm2,
+}
+
+impl FromStr for GraphicsProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "kitty" => Ok(GraphicsProtocol::Kitty),
+            "sixel" => Ok(GraphicsProtocol::Sixel),
+            "iterm" | "iterm2" => Ok(GraphicsProtocol::Iterm2),
+            other => Err(format!("unknown graphics protocol '{}' (expected kitty, sixel or iterm2)", other)),
+        }
+    }
+}
+
+impl GraphicsProtocol {
+    /// Best guess from the environment. Sixel support cannot be read from
+    /// variables reliably, so only terminals known to ship it are matched.
+    pub fn detect() -> Option<Self> {
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if env::var("KITTY_WINDOW_ID").is_ok()
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || program == "ghostty"
+            || program == "WezTerm"
+        {
+            Some(GraphicsProtocol::Kitty)
+        } else if program == "iTerm.app" {
+            Some(GraphicsProtocol::Iterm2)
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            Some(GraphicsProtocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Everything that decides what an image looks like and where it goes; an image whose key is
+/// unchanged is neither rendered nor sent again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageKey {
+    pub pane_id: usize,
+    pub area: Rect,
+    pub width: u32,
+    pub height: u32,
+    pub location: Location,
+    pub color_map: ColorMapping,
+    pub samples: u32,
+    pub jitter: bool,
+    pub interior_period: bool,
+}
+
+/// A rendered pane waiting to be written over its cells once the frame is flushed.
+pub struct PendingImage {
+    pub key: ImageKey,
+    pub area: Rect,
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+}
+
+/// Pixel size of one terminal cell, falling back to a common 10x20 when the
+/// terminal does not report its pixel dimensions.
+pub fn cell_pixels() -> (u32, u32) {
+    match window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => (10, 20),
+    }
+}
+
+pub fn flush_images<W: Write>(out: &mut W, protocol: GraphicsProtocol, images: &[PendingImage]) -> io::Result<()> {
+    if protocol == GraphicsProtocol::Kitty {
+        // Drop last frame's placements; sixel and iTerm2 images are overdrawn or cleared with the screen.
+        write!(out, "\x1b_Ga=d,d=A,q=2\x1b\\")?;
+    }
+
+    for image in images {
+        queue!(out, MoveTo(image.area.x, image.area.y))?;
+        match protocol {
+            GraphicsProtocol::Kitty => write_kitty(out, image)?,
+            GraphicsProtocol::Sixel => write_sixel(out, image)?,
+            GraphicsProtocol::Iterm2 => write_iterm2(out, image)?,
+        }
+    }
+    out.flush()
+}
+
+fn write_kitty<W: Write>(out: &mut W, image: &PendingImage) -> io::Result<()> {
+    let payload = base64(&image.rgb);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=24,s={},v={},c={},r={},C=1,q=2,m={};",
+                image.width, image.height, image.area.width, image.area.height, more
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    Ok(())
+}
+
+fn write_iterm2<W: Write>(out: &mut W, image: &PendingImage) -> io::Result<()> {
+    let png = encode_png(&image.rgb, image.width, image.height)?;
+    write!(
+        out,
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0:{}\x07",
+        png.len(), image.area.width, image.area.height, base64(&png)
+    )
+}
+
+/// Sixel has no scaling, so the image must already match the cell area in pixels.
+fn write_sixel<W: Write>(out: &mut W, image: &PendingImage) -> io::Result<()> {
+    let (palette, indices) = index_colors(&image.rgb);
+    let width = image.width as usize;
+    let height = image.height as usize;
+
+    write!(out, "\x1bPq\"1;1;{};{}", width, height)?;
+    for (i, [r, g, b]) in palette.iter().enumerate() {
+        let pct = |v: u8| v as u32 * 100 / 255;
+        write!(out, "#{};2;{};{};{}", i, pct(*r), pct(*g), pct(*b))?;
+    }
+
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        for color in 0..palette.len() {
+            let sixels: Vec<u8> = (0..width)
+                .map(|x| {
+                    (0..rows).fold(0u8, |bits, dy| {
+                        if indices[(band + dy) * width + x] as usize == color {
+                            bits | (1 << dy)
+                        } else {
+                            bits
+                        }
+                    })
+                })
+                .collect();
+            if sixels.iter().all(|&bits| bits == 0) {
+                continue;
+            }
+
+            write!(out, "#{}", color)?;
+            let mut x = 0;
+            while x < sixels.len() {
+                let run = sixels[x..].iter().take_while(|&&bits| bits == sixels[x]).count();
+                let ch = (63 + sixels[x]) as char;
+                if run > 3 {
+                    write!(out, "!{}{}", run, ch)?;
+                } else {
+                    write!(out, "{}", ch.to_string().repeat(run))?;
+                }
+                x += run;
+            }
+            write!(out, "$")?;
+        }
+        write!(out, "-")?;
+    }
+    write!(out, "\x1b\\")
+}
+
+/// Reduces RGB pixels to at most 256 registers, falling back to 3-3-2 bits
+/// per channel when the image holds more distinct colors than that.
+pub fn index_colors(rgb: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(rgb.len() / 3);
+
+    for px in rgb.chunks_exact(3) {
+        let color = [px[0], px[1], px[2]];
+        let index = match lookup.get(&color) {
+            Some(&i) => i,
+            None if palette.len() < 256 => {
+                let i = palette.len() as u8;
+                palette.push(color);
+                lookup.insert(color, i);
+                i
+            }
+            None => return index_colors_332(rgb),
+        };
+        indices.push(index);
+    }
+    (palette, indices)
+}
+
+fn index_colors_332(rgb: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let scale = |v: u32, max: u32| (v * 255 / max) as u8;
+    let palette = (0..=255u32)
+        .map(|i| [scale(i >> 5, 7), scale((i >> 2) & 7, 7), scale(i & 3, 3)])
+        .collect();
+    let indices = rgb.chunks_exact(3)
+        .map(|px| (px[0] & 0xE0) | ((px[1] >> 3) & 0x1C) | (px[2] >> 6))
+        .collect();
+    (palette, indices)
+}
+
+pub fn encode_png(rgb: &[u8], width: u32, height: u32) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgb).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+    Ok(data)
+}
+
+pub fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,214 @@
+use std::time::{Duration, Instant};
+
+use crate::hooks::{FractalPane, FractalType};
+
+/// Oldest entries are dropped past this many undo steps.
+const MAX_HISTORY: usize = 100;
+
+/// A pause this long between changes ends a run.
+const RUN_GAP: Duration = Duration::from_millis(500);
+
+/// The part of a pane that navigation changes; colors and render settings are not tracked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewState {
+    pub fractal_type: FractalType,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub scale: f64,
+    pub max_iters: u32,
+    pub auto_iters: bool,
+    pub julia_cx: f64,
+    pub julia_cy: f64,
+}
+
+impl ViewState {
+    pub fn of(pane: &FractalPane) -> Self {
+        Self {
+            fractal_type: pane.fractal_type,
+            center_x: pane.center_x,
+            center_y: pane.center_y,
+            scale: pane.scale,
+            max_iters: pane.max_iters,
+            auto_iters: pane.auto_iters,
+            julia_cx: pane.julia_cx,
+            julia_cy: pane.julia_cy,
+        }
+    }
+
+    pub fn apply(&self, pane: &mut FractalPane) {
+        pane.fractal_type = self.fractal_type;
+        pane.center_x = self.center_x;
+        pane.center_y = self.center_y;
+        pane.scale = self.scale;
+        pane.max_iters = self.max_iters;
+        pane.auto_iters = self.auto_iters;
+        pane.julia_cx = self.julia_cx;
+        pane.julia_cy = self.julia_cy;
+    }
+}
+
+/// Kinds of view change. A quick run of equal `Navigate` or `Iterations` changes is one undo step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Navigate(Motion),
+    Iterations,
+    Jump,
+}
+
+/// Which way a pan or zoom went, so turning around starts a new step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    /// Panning by key, as x and y signs.
+    Pan(i8, i8),
+    /// Zooming in or out around the mouse cell, or the center for keys.
+    Zoom { zoom_in: bool, anchor: Option<(u16, u16)> },
+}
+
+/// Back/forward stacks of view states, like a browser's.
+#[derive(Clone, Default)]
+pub struct History {
+    back: Vec<ViewState>,
+    forward: Vec<ViewState>,
+    run: Option<Change>,
+    last_change: Option<Instant>,
+}
+
+impl History {
+    /// Records `before` as an undo step, unless it continues the current run of the same change
+    /// without a pause.
+    pub fn record(&mut self, before: ViewState, change: Change) {
+        self.forward.clear();
+        let now = Instant::now();
+        let paused = self.last_change.is_none_or(|last| now.duration_since(last) >= RUN_GAP);
+        self.last_change = Some(now);
+        if change != Change::Jump && self.run == Some(change) && !paused {
+            return;
+        }
+        self.run = Some(change);
+        if self.back.len() == MAX_HISTORY {
+            self.back.remove(0);
+        }
+        self.back.push(before);
+    }
+
+    /// Ends the current run, so the next change starts a new step.
+    pub fn break_run(&mut self) {
+        self.run = None;
+    }
+
+    pub fn undo(&mut self, current: ViewState) -> Option<ViewState> {
+        self.run = None;
+        let state = self.back.pop()?;
+        self.forward.push(current);
+        Some(state)
+    }
+
+    pub fn redo(&mut self, current: ViewState) -> Option<ViewState> {
+        self.run = None;
+        let state = self.forward.pop()?;
+        self.back.push(current);
+        Some(state)
+    }
+
+    /// Steps back, oldest first, then `current`, then the steps forward; with the index of `current`.
+    pub fn timeline(&self, current: ViewState) -> (Vec<ViewState>, usize) {
+        let mut states = self.back.clone();
+        states.push(current);
+        states.extend(self.forward.iter().rev());
+        (states, self.back.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(scale: f64) -> ViewState {
+        ViewState {
+            fractal_type: FractalType::Mandelbrot,
+            center_x: -0.75,
+            center_y: 0.0,
+            scale,
+            max_iters: 100,
+            auto_iters: true,
+            julia_cx: 0.0,
+            julia_cy: 0.0,
+        }
+    }
+
+    fn zoom_in(anchor: Option<(u16, u16)>) -> Change {
+        Change::Navigate(Motion::Zoom { zoom_in: true, anchor })
+    }
+
+    fn steps(history: &History) -> usize {
+        history.back.len()
+    }
+
+    #[test]
+    fn a_quick_run_is_one_step() {
+        let mut history = History::default();
+        for i in 0..5 {
+            history.record(state(3.0 - i as f64 * 0.5), Change::Navigate(Motion::Pan(1, 0)));
+        }
+        assert_eq!(steps(&history), 1);
+        assert_eq!(history.undo(state(0.5)), Some(state(3.0)));
+    }
+
+    #[test]
+    fn a_pause_ends_the_run() {
+        let mut history = History::default();
+        history.record(state(3.0), Change::Iterations);
+        history.last_change = Some(Instant::now() - RUN_GAP);
+        history.record(state(2.0), Change::Iterations);
+        assert_eq!(steps(&history), 2);
+        history.record(state(1.0), Change::Iterations);
+        assert_eq!(steps(&history), 2);
+    }
+
+    #[test]
+    fn zooms_merge_only_around_the_same_anchor() {
+        let mut history = History::default();
+        history.record(state(3.0), zoom_in(Some((10, 5))));
+        history.record(state(2.0), zoom_in(Some((10, 5))));
+        assert_eq!(steps(&history), 1);
+        history.record(state(1.0), zoom_in(Some((11, 5))));
+        assert_eq!(steps(&history), 2);
+        history.record(state(0.5), zoom_in(None));
+        history.record(state(0.25), Change::Navigate(Motion::Zoom { zoom_in: false, anchor: None }));
+        assert_eq!(steps(&history), 4);
+    }
+
+    #[test]
+    fn jumps_never_merge() {
+        let mut history = History::default();
+        history.record(state(3.0), Change::Jump);
+        history.record(state(2.0), Change::Jump);
+        assert_eq!(steps(&history), 2);
+    }
+
+    #[test]
+    fn only_the_newest_steps_are_kept() {
+        let mut history = History::default();
+        for i in 0..MAX_HISTORY + 20 {
+            history.record(state(i as f64), Change::Jump);
+        }
+        assert_eq!(steps(&history), MAX_HISTORY);
+        let (timeline, current) = history.timeline(state(1000.0));
+        assert_eq!(current, MAX_HISTORY);
+        assert_eq!(timeline[0], state(20.0));
+    }
+
+    #[test]
+    fn a_new_change_drops_the_steps_ahead() {
+        let mut history = History::default();
+        history.record(state(3.0), Change::Jump);
+        history.record(state(2.0), Change::Jump);
+        assert_eq!(history.undo(state(1.0)), Some(state(2.0)));
+        assert_eq!(history.undo(state(2.0)), Some(state(3.0)));
+        assert_eq!(history.timeline(state(3.0)).0.len(), 3);
+
+        history.record(state(3.0), Change::Iterations);
+        assert_eq!(history.redo(state(4.0)), None);
+        assert_eq!(history.timeline(state(4.0)), (vec![state(3.0), state(4.0)], 1));
+    }
+}
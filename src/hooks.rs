@@ -1,113 +1,373 @@
-use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEvent, MouseEventKind};
+use crossterm::event::{Event, KeyCode, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use ratatui::buffer::Buffer;
 use ratatui::layout::{Rect, Direction};
-use crate::color::Palette;
+use ratatui::widgets::Block;
+use serde::{Deserialize, Serialize};
+use crate::bookmarks::{self, Bookmark};
+use crate::config::{Action, Config, Defaults};
+use crate::color::{get_palette_colors, ColorCaps, ColorMapping, Palette};
+use crate::export::{dump_text, parse_size, spawn_png, timestamp, ExportJob, TextDump};
+use crate::graphics::{GraphicsProtocol, ImageKey, PendingImage};
+use crate::history::{Change, History, Motion, ViewState};
+use crate::interop::{self, with_warnings};
+use crate::location::Location;
+use crate::render::{CellColor, RenderMode, Strategy};
+use crate::session::{self, Session};
+use crate::zoom::{parse_zoom_spec, spawn_zoom};
 
-const MAX_ITER_DEFAULT: u32 = 1100;
-
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum FractalType {
     Mandelbrot,
     BurningShip,
     Julia,
 }
 
+impl FromStr for FractalType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mandelbrot" => Ok(FractalType::Mandelbrot),
+            "burningship" | "burning-ship" | "ship" => Ok(FractalType::BurningShip),
+            "julia" => Ok(FractalType::Julia),
+            other => Err(format!("unknown fractal type '{}' (expected mandelbrot, burning-ship or julia)", other)),
+        }
+    }
+}
+
+/// The name `from_str` reads back.
+impl fmt::Display for FractalType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FractalType::Mandelbrot => "mandelbrot",
+            FractalType::BurningShip => "burning-ship",
+            FractalType::Julia => "julia",
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum InputField {
     Cx,
     Cy,
+    Export,
+    Zoom,
+    Location,
+    ImportFile,
+    ExportFile,
+}
+
+impl InputField {
+    fn accepts(self, c: char) -> bool {
+        match self {
+            InputField::Cx | InputField::Cy => c.is_ascii_digit() || c == '.' || c == '-',
+            InputField::Export | InputField::Zoom => c.is_ascii_alphanumeric() || c == ' ',
+            InputField::Location => c.is_ascii_graphic(),
+            InputField::ImportFile | InputField::ExportFile => !c.is_control(),
+        }
+    }
 }
 
-#[derive(Clone)]
+/// Runtime-only fields are skipped when a session is saved; missing fields
+/// load with their defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FractalPane {
+    #[serde(skip)]
     pub id: usize,
     pub center_x: f64,
     pub center_y: f64,
     pub scale: f64,
     pub palette: Palette,
+    pub color_map: ColorMapping,
     pub fractal_type: FractalType,
     pub max_iters: u32,
+    pub auto_iters: bool,
+    pub interior_period: bool,
+    /// Share of samples that hit `max_iters` in the last frame.
+    #[serde(skip)]
+    pub limit_ratio: f64,
+    pub render_mode: RenderMode,
+    pub cell_color: CellColor,
+    pub samples: u32,
+    pub jitter: bool,
+    pub strategy: Strategy,
+    #[serde(skip)]
     pub area: Rect,
     pub julia_cx: f64,
     pub julia_cy: f64,
+    #[serde(skip)]
     pub active_input: Option<InputField>,
+    #[serde(skip)]
     pub input_buffer: String,
+    #[serde(skip)]
+    pub export: Option<ExportJob>,
+    /// Set by a key, taken by the next draw once the pane is in the buffer.
+    #[serde(skip)]
+    pub dump: Option<TextDump>,
+    /// One-off message for the bottom border, cleared by the next key.
+    #[serde(skip)]
+    pub notice: Option<String>,
+    /// Boxed so panes stay small next to splits in `PaneNode`.
+    #[serde(skip)]
+    pub history: Box<History>,
+}
+
+impl Default for FractalPane {
+    fn default() -> Self {
+        Self::new(0, &Defaults::default())
+    }
 }
 
 impl FractalPane {
-    pub fn new(id: usize) -> Self {
+    pub fn new(id: usize, defaults: &Defaults) -> Self {
         Self {
             id,
-            center_x: -0.75,
-            center_y: 0.0,
-            scale: 3.0,
-            palette: Palette::Classic,
-            fractal_type: FractalType::Mandelbrot,
-            max_iters: MAX_ITER_DEFAULT,
+            center_x: defaults.center_x,
+            center_y: defaults.center_y,
+            scale: defaults.scale,
+            palette: defaults.palette,
+            color_map: ColorMapping::default(),
+            fractal_type: defaults.fractal_type,
+            max_iters: defaults.max_iters,
+            auto_iters: false,
+            interior_period: false,
+            limit_ratio: 0.0,
+            render_mode: RenderMode::Braille,
+            cell_color: CellColor::Majority,
+            samples: 1,
+            jitter: false,
+            strategy: Strategy::BruteForce,
             area: Rect::default(),
-            julia_cx: -0.5125,
-            julia_cy: 0.5213,
+            julia_cx: defaults.julia_cx,
+            julia_cy: defaults.julia_cy,
             active_input: None,
             input_buffer: String::new(),
+            export: None,
+            dump: None,
+            notice: None,
+            history: Box::default(),
         }
     }
 
-    fn on_key(&mut self, key: KeyCode) {
-        if let Some(field) = self.active_input {
-            match key {
-                KeyCode::Char(c) if c.is_ascii_digit() || c == '.' || c == '-' => {
-                    self.input_buffer.push(c);
-                }
-                KeyCode::Backspace => {
-                    self.input_buffer.pop();
-                }
-                KeyCode::Enter => {
-                    if let Ok(val) = self.input_buffer.parse::<f64>() {
-                        match field {
-                            InputField::Cx => self.julia_cx = val,
-                            InputField::Cy => self.julia_cy = val,
+    /// Keys typed into the active input field.
+    fn on_input_key(&mut self, field: InputField, key: KeyCode, defaults: &Defaults) {
+        match key {
+            KeyCode::Char(c) if field.accepts(c) => {
+                self.input_buffer.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input_buffer.pop();
+            }
+            KeyCode::Enter => {
+                match field {
+                    InputField::Cx | InputField::Cy => {
+                        if let Ok(val) = self.input_buffer.parse::<f64>() {
+                            let before = ViewState::of(self);
+                            if field == InputField::Cx {
+                                self.julia_cx = val;
+                            } else {
+                                self.julia_cy = val;
+                            }
+                            self.record_view(before, Change::Jump);
                         }
                     }
-                    self.active_input = None;
-                    self.input_buffer.clear();
-                }
-                KeyCode::Esc => {
-                    self.active_input = None;
-                    self.input_buffer.clear();
+                    InputField::Export => {
+                        if let Some((width, height)) = parse_size(&self.input_buffer) {
+                            self.export = Some(spawn_png(self, width, height));
+                        }
+                    }
+                    InputField::Zoom => {
+                        if let Some((frames, size, gif)) = parse_zoom_spec(&self.input_buffer) {
+                            self.export = Some(spawn_zoom(self, defaults, frames, size, gif));
+                        }
+                    }
+                    InputField::Location => match Location::decode(&self.input_buffer) {
+                        Ok(location) => self.go_to_location(&location),
+                        Err(e) => self.notice = Some(format!("Bad location: {}", e)),
+                    },
+                    InputField::ImportFile => self.import_location(&PathBuf::from(self.input_buffer.trim())),
+                    InputField::ExportFile => self.export_location(&PathBuf::from(self.input_buffer.trim())),
                 }
-                _ => {}
+                self.active_input = None;
+                self.input_buffer.clear();
             }
-            return;
+            KeyCode::Esc => {
+                self.active_input = None;
+                self.input_buffer.clear();
+            }
+            _ => {}
         }
+    }
 
-        let move_amount = self.scale * 0.1;
-        let zoom_factor = 0.9;
+    fn on_action(&mut self, action: Action, defaults: &Defaults) {
+        // A finished export's message stays up until the next key.
+        if self.export.as_ref().is_some_and(|job| !job.is_running()) {
+            self.export = None;
+        }
+        self.notice = None;
 
-        match key {
-            KeyCode::Left => self.center_x -= move_amount,
-            KeyCode::Right => self.center_x += move_amount,
-            KeyCode::Up => self.center_y += move_amount,
-            KeyCode::Down => self.center_y -= move_amount,
-            KeyCode::Char('+') | KeyCode::Char('=') => self.scale *= zoom_factor,
-            KeyCode::Char('-') | KeyCode::Char('_') => self.scale /= zoom_factor,
-            KeyCode::Char('r') => {
-                self.center_x = -0.75;
-                self.center_y = 0.0;
-                self.scale = 3.0;
-                self.max_iters = MAX_ITER_DEFAULT;
-                self.julia_cx = -0.5125;
-                self.julia_cy = 0.5213;
+        let before = ViewState::of(self);
+        let change = match action {
+            Action::PanLeft => Some(Change::Navigate(Motion::Pan(-1, 0))),
+            Action::PanRight => Some(Change::Navigate(Motion::Pan(1, 0))),
+            Action::PanUp => Some(Change::Navigate(Motion::Pan(0, 1))),
+            Action::PanDown => Some(Change::Navigate(Motion::Pan(0, -1))),
+            Action::ZoomIn => Some(Change::Navigate(Motion::Zoom { zoom_in: true, anchor: None })),
+            Action::ZoomOut => Some(Change::Navigate(Motion::Zoom { zoom_in: false, anchor: None })),
+            Action::MoreIters | Action::FewerIters | Action::AutoIters => Some(Change::Iterations),
+            Action::Reset | Action::CycleFractal => Some(Change::Jump),
+            _ => None,
+        };
+
+        let move_amount = self.scale * defaults.pan_step;
+
+        match action {
+            Action::PanLeft => self.center_x -= move_amount,
+            Action::PanRight => self.center_x += move_amount,
+            Action::PanUp => self.center_y += move_amount,
+            Action::PanDown => self.center_y -= move_amount,
+            Action::ZoomIn => self.scale *= defaults.zoom_factor,
+            Action::ZoomOut => self.scale /= defaults.zoom_factor,
+            Action::Reset => {
+                self.center_x = defaults.center_x;
+                self.center_y = defaults.center_y;
+                self.scale = defaults.scale;
+                self.max_iters = defaults.max_iters;
+                self.julia_cx = defaults.julia_cx;
+                self.julia_cy = defaults.julia_cy;
                 self.active_input = None;
                 self.input_buffer.clear();
             }
-            KeyCode::Char(' ') => self.toggle_palette(),
-            KeyCode::Char('b') => self.toggle_fractal_type(),
-            KeyCode::Char('d') => self.max_iters = self.max_iters.saturating_add(10),
-            KeyCode::Char('s') => self.max_iters = self.max_iters.saturating_sub(10).max(10),
+            Action::NextPalette => self.toggle_palette(),
+            Action::RotatePaletteBack | Action::RotatePaletteForward => {
+                let palette_len = get_palette_colors(self.palette).len();
+                self.color_map.rotate(action == Action::RotatePaletteForward, palette_len);
+            }
+            Action::DensityUp => self.color_map.density = (self.color_map.density * 1.25).min(1000.0),
+            Action::DensityDown => self.color_map.density = (self.color_map.density / 1.25).max(0.01),
+            Action::ReversePalette => self.color_map.reverse = !self.color_map.reverse,
+            Action::CycleTransfer => self.color_map.toggle_transfer(),
+            Action::CycleFractal => self.toggle_fractal_type(),
+            Action::CellColor => self.cell_color = self.cell_color.toggle(),
+            Action::Supersampling => self.samples = self.samples % 4 + 1,
+            Action::Jitter => self.jitter = !self.jitter,
+            Action::FillStrategy => self.strategy = self.strategy.toggle(),
+            Action::MoreIters => {
+                self.auto_iters = false;
+                self.max_iters = self.max_iters.saturating_add(10);
+            }
+            Action::FewerIters => {
+                self.auto_iters = false;
+                self.max_iters = self.max_iters.saturating_sub(10).max(10);
+            }
+            Action::AutoIters => self.auto_iters = !self.auto_iters,
+            Action::InteriorPeriod => self.interior_period = !self.interior_period,
+            Action::ExportPng if self.export.is_none() => {
+                self.active_input = Some(InputField::Export);
+                self.input_buffer = "3840x2160".to_string();
+            }
+            Action::ExportZoom if self.export.is_none() => {
+                self.active_input = Some(InputField::Zoom);
+                self.input_buffer = "60 640x360 gif".to_string();
+            }
+            Action::PasteLocation => {
+                self.active_input = Some(InputField::Location);
+                self.input_buffer.clear();
+            }
+            Action::ImportLocation => {
+                self.active_input = Some(InputField::ImportFile);
+                self.input_buffer.clear();
+            }
+            Action::ExportLocation => {
+                self.active_input = Some(InputField::ExportFile);
+                self.input_buffer = format!("fractal-{}.kfr", timestamp());
+            }
+            Action::DumpAnsi => self.dump = Some(TextDump::Ansi),
+            Action::DumpText => self.dump = Some(TextDump::Plain),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
             _ => {}
         }
+
+        match change {
+            Some(change) => self.record_view(before, change),
+            None => self.history.break_run(),
+        }
+    }
+
+    /// Adds an undo step if the view moved away from `before`.
+    pub fn record_view(&mut self, before: ViewState, change: Change) {
+        if ViewState::of(self) != before {
+            self.history.record(before, change);
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(state) = self.history.undo(ViewState::of(self)) {
+            state.apply(self);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(state) = self.history.redo(ViewState::of(self)) {
+            state.apply(self);
+        }
+    }
+
+    /// Jumps to `location` as a single undo step.
+    pub fn go_to_location(&mut self, location: &Location) {
+        let before = ViewState::of(self);
+        location.apply(self);
+        self.record_view(before, Change::Jump);
+    }
+
+    fn import_location(&mut self, path: &Path) {
+        let mut location = Location::of(self);
+        let imported = interop::import(path, &mut location)
+            .and_then(|warnings| match location.validate() {
+                Ok(()) => Ok(warnings),
+                Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))),
+            });
+        self.notice = Some(match imported {
+            Ok(warnings) => {
+                self.go_to_location(&location);
+                with_warnings(format!("Imported {}", path.display()), &warnings)
+            }
+            Err(e) => format!("Import failed: {}", e),
+        });
+    }
+
+    fn export_location(&mut self, path: &Path) {
+        self.notice = Some(match interop::export(path, &Location::of(self)) {
+            Ok(warnings) => with_warnings(format!("Saved {}", path.display()), &warnings),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+    /// Pasted text goes into the open prompt, minus characters the prompt does not take.
+    fn paste(&mut self, text: &str) {
+        if let Some(field) = self.active_input {
+            self.input_buffer.extend(text.chars().filter(|&c| field.accepts(c)));
+        }
     }
 
-    fn on_mouse(&mut self, mouse: MouseEvent) {
+    /// Undoes or redoes until the view is entry `index` of the history timeline.
+    pub fn go_to_history(&mut self, index: usize) {
+        let (_, current) = self.history.timeline(ViewState::of(self));
+        for _ in index..current {
+            self.undo();
+        }
+        for _ in current..index {
+            self.redo();
+        }
+    }
+
+    fn on_mouse(&mut self, mouse: MouseEvent, defaults: &Defaults) {
         let area = self.area;
         let x = mouse.column;
         let y = mouse.row;
@@ -116,6 +376,11 @@ impl FractalPane {
             return;
         }
 
+        // A click ends a run of scrolls, so the next scroll starts a new undo step.
+        if let MouseEventKind::Down(_) = mouse.kind {
+            self.history.break_run();
+        }
+
         if self.fractal_type == FractalType::Julia {
             // Inputs at bottom-3 (Cx) and bottom-2 (Cy)
             let bottom = area.bottom();
@@ -142,24 +407,36 @@ impl FractalPane {
         let mouse_world_x = (self.center_x - math_width / 2.0) + norm_x * math_width;
         let mouse_world_y = (self.center_y + self.scale / 2.0) - norm_y * self.scale;
 
-        match mouse.kind {
+        let before = ViewState::of(self);
+        let zoom_in = match mouse.kind {
             MouseEventKind::ScrollUp => {
-                let new_scale = self.scale * 0.90;
+                let new_scale = self.scale * defaults.zoom_factor;
                 if new_scale < 1.0e-14 { return; }
                 let new_width = new_scale * aspect_ratio;
                 self.center_x = mouse_world_x - (norm_x - 0.5) * new_width;
                 self.center_y = mouse_world_y - (0.5 - norm_y) * new_scale;
                 self.scale = new_scale;
+                true
             }
             MouseEventKind::ScrollDown => {
-                let new_scale = self.scale * 1.10;
+                let new_scale = self.scale / defaults.zoom_factor;
                 let new_width = new_scale * aspect_ratio;
                 self.center_x = mouse_world_x - (norm_x - 0.5) * new_width;
                 self.center_y = mouse_world_y - (0.5 - norm_y) * new_scale;
                 self.scale = new_scale;
+                false
             }
-            _ => {}
-        }
+            _ => return,
+        };
+        self.record_view(before, Change::Navigate(Motion::Zoom { zoom_in, anchor: Some((x, y)) }));
+    }
+
+    /// Iteration budget for auto mode: grows with the zoom depth, and up to
+    /// twice that when most of the last frame ran into the limit.
+    pub fn auto_max_iters(&self) -> u32 {
+        let depth = (3.0 / self.scale).log2().max(0.0);
+        let base = 200.0 + 150.0 * depth;
+        (base * (1.0 + self.limit_ratio)).clamp(50.0, 100_000.0) as u32
     }
 
     pub fn toggle_palette(&mut self) {
@@ -179,14 +456,216 @@ impl FractalPane {
     }
 }
 
+/// ratatui's `Direction` has no serde support, so sessions store it by name.
+mod direction_name {
+    use ratatui::layout::Direction;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(direction: &Direction, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(direction)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Direction, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Panes are not resized below this many cells by dividers or resize keys.
+const MIN_PANE_SIZE: u16 = 4;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PaneNode {
     Pane(FractalPane),
     Split {
+        #[serde(with = "direction_name")]
         direction: Direction,
         children: Vec<PaneNode>,
+        /// Relative sizes of `children`; sessions without them split evenly.
+        #[serde(default)]
+        weights: Vec<u16>,
     },
 }
 
+/// The border between two neighboring children of a split: the split's child indices from
+/// the root, and the child before the border.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divider {
+    path: Vec<usize>,
+    index: usize,
+}
+
+impl PaneNode {
+    /// A split with equally sized children.
+    pub fn split(direction: Direction, children: Vec<PaneNode>) -> Self {
+        let weights = vec![1; children.len()];
+        PaneNode::Split { direction, children, weights }
+    }
+
+    /// The screen area covered by this node as of the last draw.
+    fn bounds(&self) -> Rect {
+        match self {
+            PaneNode::Pane(p) => p.area,
+            PaneNode::Split { children, .. } => {
+                children.iter().map(PaneNode::bounds).reduce(|a, b| a.union(b)).unwrap_or_default()
+            }
+        }
+    }
+
+    /// The node reached by following child indices from here.
+    fn node_at(&self, path: &[usize]) -> Option<&PaneNode> {
+        match path.split_first() {
+            None => Some(self),
+            Some((&i, rest)) => match self {
+                PaneNode::Split { children, .. } => children.get(i)?.node_at(rest),
+                PaneNode::Pane(_) => None,
+            },
+        }
+    }
+
+    fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut PaneNode> {
+        match path.split_first() {
+            None => Some(self),
+            Some((&i, rest)) => match self {
+                PaneNode::Split { children, .. } => children.get_mut(i)?.node_at_mut(rest),
+                PaneNode::Pane(_) => None,
+            },
+        }
+    }
+
+    /// Child indices leading to the pane with `target_id`.
+    fn path_to(&self, target_id: usize, path: &mut Vec<usize>) -> bool {
+        match self {
+            PaneNode::Pane(p) => p.id == target_id,
+            PaneNode::Split { children, .. } => children.iter().enumerate().any(|(i, child)| {
+                path.push(i);
+                child.path_to(target_id, path) || {
+                    path.pop();
+                    false
+                }
+            }),
+        }
+    }
+
+    /// The divider under a screen cell; panes share no cells, so it is either neighbor's border.
+    fn divider_at(&self, col: u16, row: u16, path: &mut Vec<usize>) -> Option<Divider> {
+        let PaneNode::Split { direction, children, .. } = self else {
+            return None;
+        };
+        for (i, child) in children.iter().enumerate() {
+            path.push(i);
+            let inner = child.divider_at(col, row, path);
+            path.pop();
+            if inner.is_some() {
+                return inner;
+            }
+        }
+        children.windows(2).position(|pair| {
+            let (a, b) = (pair[0].bounds(), pair[1].bounds());
+            match direction {
+                Direction::Horizontal => (col + 1 == a.right() || col == b.x) && row >= a.y && row < a.bottom(),
+                Direction::Vertical => (row + 1 == a.bottom() || row == b.y) && col >= a.x && col < a.right(),
+            }
+        }).map(|index| Divider { path: path.clone(), index })
+    }
+
+    /// Moves the border after child `index` so that child gets `size` cells, at least
+    /// `MIN_PANE_SIZE` on both sides. Weights become the drawn sizes, so nothing else moves.
+    fn move_divider(&mut self, index: usize, size: i32) {
+        let PaneNode::Split { direction, children, weights } = self else {
+            return;
+        };
+        let sizes: Vec<u16> = children.iter()
+            .map(|child| {
+                let area = child.bounds();
+                if *direction == Direction::Horizontal { area.width } else { area.height }
+            })
+            .collect();
+        if index + 1 >= sizes.len() || sizes.contains(&0) {
+            return;
+        }
+        let total = sizes[index] + sizes[index + 1];
+        if total < 2 * MIN_PANE_SIZE {
+            return;
+        }
+        let size = size.clamp(MIN_PANE_SIZE as i32, (total - MIN_PANE_SIZE) as i32) as u16;
+        *weights = sizes;
+        weights[index] = size;
+        weights[index + 1] = total - size;
+    }
+
+    fn equalize(&mut self) {
+        if let PaneNode::Split { children, weights, .. } = self {
+            weights.iter_mut().for_each(|w| *w = 1);
+            children.iter_mut().for_each(PaneNode::equalize);
+        }
+    }
+}
+
+/// Split arrangements to start with; every pane begins as a copy of the same pane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    Single,
+    /// Two panes side by side.
+    Columns,
+    /// Two panes stacked.
+    Rows,
+    /// Two by two.
+    Grid,
+    /// One pane on the left, two stacked on the right.
+    MainStack,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "single" => Ok(Layout::Single),
+            "columns" => Ok(Layout::Columns),
+            "rows" => Ok(Layout::Rows),
+            "grid" => Ok(Layout::Grid),
+            "main-stack" | "mainstack" => Ok(Layout::MainStack),
+            other => Err(format!("unknown layout '{}' (expected single, columns, rows, grid or main-stack)", other)),
+        }
+    }
+}
+
+impl Layout {
+    /// The split tree with copies of `pane`; ids are assigned when the app takes it over.
+    pub fn build(self, pane: &FractalPane) -> PaneNode {
+        let leaf = || PaneNode::Pane(pane.clone());
+        let split = PaneNode::split;
+        match self {
+            Layout::Single => leaf(),
+            Layout::Columns => split(Direction::Horizontal, vec![leaf(), leaf()]),
+            Layout::Rows => split(Direction::Vertical, vec![leaf(), leaf()]),
+            Layout::Grid => split(Direction::Vertical, vec![
+                split(Direction::Horizontal, vec![leaf(), leaf()]),
+                split(Direction::Horizontal, vec![leaf(), leaf()]),
+            ]),
+            Layout::MainStack => split(Direction::Horizontal, vec![
+                leaf(),
+                split(Direction::Vertical, vec![leaf(), leaf()]),
+            ]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FocusDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// What the bookmark name prompt is for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookmarkInput {
+    New,
+    Rename,
+}
+
 pub struct App {
     pub root: PaneNode,
     pub active_pane_id: usize,
@@ -194,43 +673,315 @@ pub struct App {
     pub should_quit: bool,
     pub show_quit_popup: bool,
     pub show_help_popup: bool,
+    /// First help row shown; the list of bindings can be taller than the popup.
+    pub help_scroll: usize,
+    pub color_caps: ColorCaps,
+    pub graphics: Option<GraphicsProtocol>,
+    /// Images of the last frame, kept so unchanged panes reuse their pixels.
+    pub pending_images: Vec<PendingImage>,
+    /// What the terminal currently shows, so unchanged frames are not sent again.
+    pub shown_images: Vec<ImageKey>,
+    /// Whether the terminal output should be captured to an asciicast file.
+    pub recording: bool,
+    /// The divider held with the mouse, if any.
+    pub dragging: Option<Divider>,
+    /// Text for the main loop to put on the system clipboard.
+    pub clipboard: Option<String>,
+    /// Where Ctrl+S/Ctrl+O save and load the session.
+    pub session_path: PathBuf,
+    /// Save the session on quit.
+    pub autosave: bool,
+    pub bookmarks: Vec<Bookmark>,
+    pub bookmarks_path: PathBuf,
+    pub show_bookmarks: bool,
+    pub bookmark_selected: usize,
+    pub bookmark_input: Option<BookmarkInput>,
+    pub bookmark_name: String,
+    pub show_history: bool,
+    /// Row of the active pane's history timeline picked in the popup.
+    pub history_selected: usize,
+    /// Defaults for new panes and the key bindings.
+    pub config: Config,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(color_caps: ColorCaps, graphics: Option<GraphicsProtocol>, config: Config) -> Self {
         Self {
-            root: PaneNode::Pane(FractalPane::new(0)),
+            root: PaneNode::Pane(FractalPane::new(0, &config.defaults)),
             active_pane_id: 0,
             next_id: 1,
             should_quit: false,
             show_quit_popup: false,
             show_help_popup: false,
+            help_scroll: 0,
+            color_caps,
+            graphics,
+            pending_images: Vec::new(),
+            shown_images: Vec::new(),
+            recording: false,
+            dragging: None,
+            clipboard: None,
+            session_path: session::default_path(),
+            autosave: false,
+            bookmarks: Vec::new(),
+            bookmarks_path: bookmarks::default_path(),
+            show_bookmarks: false,
+            bookmark_selected: 0,
+            bookmark_input: None,
+            bookmark_name: String::new(),
+            show_history: false,
+            history_selected: 0,
+            config,
         }
     }
 
-    fn send_key_to_active(&mut self, key: KeyCode) {
-        Self::traverse_and_handle_key(&mut self.root, self.active_pane_id, key);
+    pub fn session(&self) -> Session {
+        let mut ids = Vec::new();
+        Self::collect_ids(&self.root, &mut ids);
+        Session {
+            active: ids.iter().position(|&id| id == self.active_pane_id).unwrap_or(0),
+            root: self.root.clone(),
+        }
+    }
+
+    /// Replaces the layout with a loaded session, numbering panes in visual order.
+    pub fn restore(&mut self, session: Session) {
+        self.root = session.root;
+        let mut count = 0;
+        Self::renumber(&mut self.root, &mut count);
+        if count == 0 {
+            self.root = PaneNode::Pane(FractalPane::new(0, &self.config.defaults));
+            count = 1;
+        }
+        self.next_id = count;
+        self.active_pane_id = session.active.min(count - 1);
     }
 
-    fn traverse_and_handle_key(node: &mut PaneNode, target_id: usize, key: KeyCode) {
+    fn renumber(node: &mut PaneNode, next_id: &mut usize) {
         match node {
             PaneNode::Pane(p) => {
-                if p.id == target_id {
-                    p.on_key(key);
+                p.id = *next_id;
+                *next_id += 1;
+            }
+            PaneNode::Split { children, weights, .. } => {
+                // Hand-edited or older sessions may have missing or zero weights.
+                weights.resize(children.len(), 1);
+                weights.iter_mut().for_each(|w| *w = (*w).max(1));
+                for child in children {
+                    Self::renumber(child, next_id);
+                }
+            }
+        }
+    }
+
+    fn save_session(&mut self) {
+        let message = match session::save(&self.session(), &self.session_path) {
+            Ok(()) => format!("Saved session to {}", self.session_path.display()),
+            Err(e) => format!("Saving session failed: {}", e),
+        };
+        self.notify(message);
+    }
+
+    fn load_session(&mut self) {
+        let message = match session::load(&self.session_path) {
+            Ok(session) => {
+                self.restore(session);
+                format!("Loaded session from {}", self.session_path.display())
+            }
+            Err(e) => format!("Loading session failed: {}", e),
+        };
+        self.notify(message);
+    }
+
+    fn save_bookmarks(&mut self) -> bool {
+        match bookmarks::save(&self.bookmarks_path, &self.bookmarks) {
+            Ok(()) => true,
+            Err(e) => {
+                self.notify(format!("Saving bookmarks failed: {}", e));
+                false
+            }
+        }
+    }
+
+    fn on_bookmark_name_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char(c) if !c.is_control() => self.bookmark_name.push(c),
+            KeyCode::Backspace => {
+                self.bookmark_name.pop();
+            }
+            KeyCode::Enter => {
+                let name = self.bookmark_name.trim().to_string();
+                match self.bookmark_input.take() {
+                    _ if name.is_empty() => {}
+                    Some(BookmarkInput::New) => {
+                        if let Some(bookmark) = self.active_pane().map(|pane| Bookmark { name: name.clone(), location: Location::of(pane) }) {
+                            self.bookmarks.push(bookmark);
+                            self.bookmark_selected = self.bookmarks.len() - 1;
+                            if self.save_bookmarks() {
+                                self.notify(format!("Bookmarked {}", name));
+                            }
+                        }
+                    }
+                    Some(BookmarkInput::Rename) => {
+                        if let Some(bookmark) = self.bookmarks.get_mut(self.bookmark_selected) {
+                            bookmark.name = name;
+                            self.save_bookmarks();
+                        }
+                    }
+                    None => {}
+                }
+                self.bookmark_name.clear();
+            }
+            KeyCode::Esc => {
+                self.bookmark_input = None;
+                self.bookmark_name.clear();
+            }
+            _ => {}
+        }
+    }
+
+    fn on_bookmarks_key(&mut self, key: KeyCode, action: Option<Action>) {
+        match key {
+            KeyCode::Up => self.bookmark_selected = self.bookmark_selected.saturating_sub(1),
+            KeyCode::Down => {
+                self.bookmark_selected = (self.bookmark_selected + 1).min(self.bookmarks.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                if let Some(bookmark) = self.bookmarks.get(self.bookmark_selected).cloned() {
+                    if let Some(pane) = self.active_pane_mut() {
+                        let before = ViewState::of(pane);
+                        bookmark.location.apply(pane);
+                        pane.record_view(before, Change::Jump);
+                    }
+                    self.show_bookmarks = false;
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(bookmark) = self.bookmarks.get(self.bookmark_selected) {
+                    self.bookmark_name = bookmark.name.clone();
+                    self.bookmark_input = Some(BookmarkInput::Rename);
+                }
+            }
+            KeyCode::Char('d') if self.bookmark_selected < self.bookmarks.len() => {
+                self.bookmarks.remove(self.bookmark_selected);
+                self.bookmark_selected = self.bookmark_selected.min(self.bookmarks.len().saturating_sub(1));
+                self.save_bookmarks();
+            }
+            KeyCode::Esc => self.show_bookmarks = false,
+            _ if matches!(action, Some(Action::Bookmarks | Action::Quit)) => self.show_bookmarks = false,
+            _ => {}
+        }
+    }
+
+    fn open_history(&mut self) {
+        if let Some(pane) = self.active_pane() {
+            self.history_selected = pane.history.timeline(ViewState::of(pane)).1;
+            self.show_history = true;
+        }
+    }
+
+    fn on_history_key(&mut self, key: KeyCode, action: Option<Action>) {
+        let entries = self.active_pane().map_or(0, |pane| pane.history.timeline(ViewState::of(pane)).0.len());
+        match key {
+            KeyCode::Up => self.history_selected = self.history_selected.saturating_sub(1),
+            KeyCode::Down => self.history_selected = (self.history_selected + 1).min(entries.saturating_sub(1)),
+            KeyCode::Enter => {
+                let index = self.history_selected;
+                if let Some(pane) = self.active_pane_mut() {
+                    pane.go_to_history(index);
                 }
+                self.show_history = false;
             }
+            KeyCode::Esc => self.show_history = false,
+            _ if matches!(action, Some(Action::History | Action::Quit)) => self.show_history = false,
+            _ => {}
+        }
+    }
+
+    /// Shows a message on the active pane's bottom border until its next key.
+    pub fn notify(&mut self, message: String) {
+        if let Some(pane) = self.active_pane_mut() {
+            pane.notice = Some(message);
+        }
+    }
+
+    pub fn active_pane(&self) -> Option<&FractalPane> {
+        Self::find_pane(&self.root, self.active_pane_id)
+    }
+
+    fn find_pane(node: &PaneNode, target_id: usize) -> Option<&FractalPane> {
+        match node {
+            PaneNode::Pane(p) => (p.id == target_id).then_some(p),
             PaneNode::Split { children, .. } => {
-                for child in children {
-                    Self::traverse_and_handle_key(child, target_id, key);
+                children.iter().find_map(|child| Self::find_pane(child, target_id))
+            }
+        }
+    }
+
+    fn active_pane_mut(&mut self) -> Option<&mut FractalPane> {
+        Self::find_pane_mut(&mut self.root, self.active_pane_id)
+    }
+
+    fn find_pane_mut(node: &mut PaneNode, target_id: usize) -> Option<&mut FractalPane> {
+        match node {
+            PaneNode::Pane(p) => (p.id == target_id).then_some(p),
+            PaneNode::Split { children, .. } => {
+                children.iter_mut().find_map(|child| Self::find_pane_mut(child, target_id))
+            }
+        }
+    }
+
+    fn cycle_render_mode(&mut self) {
+        let pixels = self.graphics.is_some();
+        if let Some(pane) = self.active_pane_mut() {
+            pane.render_mode = pane.render_mode.next(pixels);
+        }
+    }
+
+    fn send_key_to_active(&mut self, key: KeyCode) {
+        if let Some(pane) = Self::find_pane_mut(&mut self.root, self.active_pane_id) {
+            if let Some(field) = pane.active_input {
+                pane.on_input_key(field, key, &self.config.defaults);
+            }
+        }
+    }
+
+    fn copy_location(&mut self) {
+        if let Some(text) = self.active_pane().map(|pane| Location::of(pane).encode()) {
+            self.notify(format!("Copied {}", text));
+            self.clipboard = Some(text);
+        }
+    }
+
+    /// Pastes into the active prompt; with none open, a pasted location string is jumped to.
+    fn on_paste(&mut self, text: &str) {
+        if self.is_active_pane_capturing_input() {
+            if let Some(pane) = self.active_pane_mut() {
+                pane.paste(text);
+            }
+            return;
+        }
+        match Location::decode(text) {
+            Ok(location) => {
+                if let Some(pane) = self.active_pane_mut() {
+                    pane.go_to_location(&location);
+                    pane.notice = Some("Jumped to pasted location".to_string());
                 }
             }
+            Err(e) => self.notify(format!("Ignored paste: {}", e)),
+        }
+    }
+
+    fn send_action_to_active(&mut self, action: Action) {
+        if let Some(pane) = Self::find_pane_mut(&mut self.root, self.active_pane_id) {
+            pane.on_action(action, &self.config.defaults);
         }
     }
 
     fn split_active(&mut self, direction: Direction, prepend: bool) {
         let new_id = self.next_id;
         self.next_id += 1;
-        let new_pane = FractalPane::new(new_id);
+        let new_pane = FractalPane::new(new_id, &self.config.defaults);
 
         if Self::recursive_split(&mut self.root, self.active_pane_id, direction, prepend, new_pane) {
             self.active_pane_id = new_id;
@@ -251,7 +1002,7 @@ impl App {
                         vec![current_leaf, new_leaf]
                     };
 
-                    *node = PaneNode::Split { direction, children };
+                    *node = PaneNode::split(direction, children);
                     return true;
                 }
                 false
@@ -270,12 +1021,13 @@ impl App {
     fn recursive_delete(node: &mut PaneNode, target_id: usize) -> bool {
         match node {
             PaneNode::Pane(_) => false,
-            PaneNode::Split { children, .. } => {
+            PaneNode::Split { children, weights, .. } => {
                 if let Some(pos) = children.iter().position(|child| match child {
                     PaneNode::Pane(p) => p.id == target_id,
                     _ => false
                 }) {
                     children.remove(pos);
+                    weights.remove(pos);
                     true
                 } else {
                     let mut deleted = false;
@@ -296,6 +1048,17 @@ impl App {
         }
     }
 
+    /// Removes a pane from the tree, collapsing splits left with one child.
+    fn detach(&mut self, target_id: usize) {
+        if Self::recursive_delete(&mut self.root, target_id) {
+            if let PaneNode::Split { children, .. } = &mut self.root {
+                if children.len() == 1 {
+                    self.root = children.pop().unwrap();
+                }
+            }
+        }
+    }
+
     fn close_active(&mut self) {
         let mut ids = Vec::new();
         Self::collect_ids(&self.root, &mut ids);
@@ -303,13 +1066,7 @@ impl App {
             return;
         }
 
-        if Self::recursive_delete(&mut self.root, self.active_pane_id) {
-            if let PaneNode::Split { children, .. } = &mut self.root {
-                if children.len() == 1 {
-                    self.root = children.pop().unwrap();
-                }
-            }
-        }
+        self.detach(self.active_pane_id);
 
         if !self.pane_exists(self.active_pane_id) {
             let mut new_ids = Vec::new();
@@ -320,6 +1077,63 @@ impl App {
         }
     }
 
+    /// Grows or shrinks the active pane along `direction` by moving the nearest divider of
+    /// the innermost split in that direction.
+    fn resize_active(&mut self, direction: Direction, grow: bool) {
+        let mut path = Vec::new();
+        if !self.root.path_to(self.active_pane_id, &mut path) {
+            return;
+        }
+        for depth in (0..path.len()).rev() {
+            let Some(split) = self.root.node_at_mut(&path[..depth]) else {
+                return;
+            };
+            let PaneNode::Split { direction: split_direction, children, .. } = &*split else {
+                return;
+            };
+            if *split_direction != direction {
+                continue;
+            }
+            let child = path[depth];
+            let span = split.bounds();
+            let span = if direction == Direction::Horizontal { span.width } else { span.height };
+            let step = (span / 20).max(1) as i32;
+            let size = |node: &PaneNode| {
+                let area = node.bounds();
+                (if direction == Direction::Horizontal { area.width } else { area.height }) as i32
+            };
+            // The last child has no border after it, so it moves the one before.
+            if child + 1 < children.len() {
+                let size = size(&children[child]) + if grow { step } else { -step };
+                split.move_divider(child, size);
+            } else {
+                let size = size(&children[child - 1]) + if grow { -step } else { step };
+                split.move_divider(child - 1, size);
+            }
+            return;
+        }
+    }
+
+    fn on_divider_drag(&mut self, mouse: MouseEvent) {
+        let Some(divider) = &self.dragging else {
+            return;
+        };
+        let Some(split) = self.root.node_at_mut(&divider.path) else {
+            return;
+        };
+        let PaneNode::Split { direction, children, .. } = &*split else {
+            return;
+        };
+        let Some(first) = children.get(divider.index).map(PaneNode::bounds) else {
+            return;
+        };
+        let size = match direction {
+            Direction::Horizontal => mouse.column as i32 - first.x as i32 + 1,
+            Direction::Vertical => mouse.row as i32 - first.y as i32 + 1,
+        };
+        split.move_divider(divider.index, size);
+    }
+
     fn cycle_focus(&mut self) {
         let mut ids = Vec::new();
         Self::collect_ids(&self.root, &mut ids);
@@ -341,6 +1155,157 @@ impl App {
         }
     }
 
+    fn collect_areas(node: &PaneNode, areas: &mut Vec<(usize, Rect)>) {
+        match node {
+            PaneNode::Pane(p) => areas.push((p.id, p.area)),
+            PaneNode::Split { children, .. } => {
+                for child in children {
+                    Self::collect_areas(child, areas);
+                }
+            }
+        }
+    }
+
+    /// The closest pane on the given side of the active one, going by the areas of the last
+    /// draw. Panes that line up with the active one win over nearer diagonal ones.
+    fn neighbor_toward(&self, toward: FocusDirection) -> Option<usize> {
+        let mut areas = Vec::new();
+        Self::collect_areas(&self.root, &mut areas);
+        let &(_, from) = areas.iter().find(|(id, _)| *id == self.active_pane_id)?;
+        // Distance between two ranges on a line; 0 when they overlap.
+        let gap = |a0: u16, a1: u16, b0: u16, b1: u16| b0.saturating_sub(a1).max(a0.saturating_sub(b1));
+        let center = |start: u16, len: u16| 2 * start as i32 + len as i32;
+
+        areas.iter()
+            .filter(|&&(id, _)| id != self.active_pane_id)
+            .filter_map(|&(id, to)| {
+                let (ahead, across, offset) = match toward {
+                    FocusDirection::Left if to.right() <= from.left() => (
+                        from.left() - to.right(),
+                        gap(from.top(), from.bottom(), to.top(), to.bottom()),
+                        center(to.y, to.height) - center(from.y, from.height),
+                    ),
+                    FocusDirection::Right if to.left() >= from.right() => (
+                        to.left() - from.right(),
+                        gap(from.top(), from.bottom(), to.top(), to.bottom()),
+                        center(to.y, to.height) - center(from.y, from.height),
+                    ),
+                    FocusDirection::Up if to.bottom() <= from.top() => (
+                        from.top() - to.bottom(),
+                        gap(from.left(), from.right(), to.left(), to.right()),
+                        center(to.x, to.width) - center(from.x, from.width),
+                    ),
+                    FocusDirection::Down if to.top() >= from.bottom() => (
+                        to.top() - from.bottom(),
+                        gap(from.left(), from.right(), to.left(), to.right()),
+                        center(to.x, to.width) - center(from.x, from.width),
+                    ),
+                    _ => return None,
+                };
+                Some(((across, ahead, offset.abs()), id))
+            })
+            .min()
+            .map(|(_, id)| id)
+    }
+
+    fn focus_toward(&mut self, toward: FocusDirection) {
+        if let Some(id) = self.neighbor_toward(toward) {
+            self.active_pane_id = id;
+        }
+    }
+
+    /// Trades places with the neighboring pane; both keep their state and focus stays on the
+    /// active pane.
+    fn swap_toward(&mut self, toward: FocusDirection) {
+        let Some(other_id) = self.neighbor_toward(toward) else {
+            return;
+        };
+        let (mut active_path, mut other_path) = (Vec::new(), Vec::new());
+        if !self.root.path_to(self.active_pane_id, &mut active_path) || !self.root.path_to(other_id, &mut other_path) {
+            return;
+        }
+        let (Some(active), Some(other)) = (
+            self.root.node_at(&active_path).cloned(),
+            self.root.node_at(&other_path).cloned(),
+        ) else {
+            return;
+        };
+        if let Some(slot) = self.root.node_at_mut(&active_path) {
+            *slot = other;
+        }
+        if let Some(slot) = self.root.node_at_mut(&other_path) {
+            *slot = active;
+        }
+    }
+
+    /// Takes the active pane out of its split and splits the neighboring pane with it, on the
+    /// far side, so it moves past the neighbor. Without a neighbor it becomes a full-length
+    /// column or row at that edge of the layout.
+    fn move_toward(&mut self, toward: FocusDirection) {
+        let mut ids = Vec::new();
+        Self::collect_ids(&self.root, &mut ids);
+        if ids.len() <= 1 {
+            return;
+        }
+        let target = self.neighbor_toward(toward);
+        let Some(pane) = self.active_pane().cloned() else {
+            return;
+        };
+        let (direction, before) = match toward {
+            FocusDirection::Left => (Direction::Horizontal, true),
+            FocusDirection::Right => (Direction::Horizontal, false),
+            FocusDirection::Up => (Direction::Vertical, true),
+            FocusDirection::Down => (Direction::Vertical, false),
+        };
+
+        self.detach(pane.id);
+        if let Some(target) = target {
+            Self::recursive_split(&mut self.root, target, direction, before, pane);
+            return;
+        }
+        if let PaneNode::Split { direction: root_direction, children, weights } = &mut self.root {
+            if *root_direction == direction {
+                // An average share, like the panes an even split would give.
+                let weight = weights.iter().map(|&w| w as u32).sum::<u32>() / weights.len() as u32;
+                let index = if before { 0 } else { children.len() };
+                children.insert(index, PaneNode::Pane(pane));
+                weights.insert(index, weight.max(1) as u16);
+                return;
+            }
+        }
+        let old = std::mem::replace(&mut self.root, PaneNode::split(direction, Vec::new()));
+        let children = if before { vec![PaneNode::Pane(pane), old] } else { vec![old, PaneNode::Pane(pane)] };
+        self.root = PaneNode::split(direction, children);
+    }
+
+    /// The innermost split holding the active pane.
+    fn active_split_mut(&mut self) -> Option<&mut PaneNode> {
+        let mut path = Vec::new();
+        if !self.root.path_to(self.active_pane_id, &mut path) {
+            return None;
+        }
+        path.pop()?;
+        self.root.node_at_mut(&path)
+    }
+
+    /// Shifts every pane of the active split one place forward; the last one comes first.
+    fn rotate_split(&mut self) {
+        if let Some(PaneNode::Split { children, weights, .. }) = self.active_split_mut() {
+            children.rotate_right(1);
+            weights.rotate_right(1);
+        }
+    }
+
+    /// Turns the active split from side by side into stacked, or back.
+    fn flip_split(&mut self) {
+        if let Some(PaneNode::Split { direction, .. }) = self.active_split_mut() {
+            *direction = match direction {
+                Direction::Horizontal => Direction::Vertical,
+                Direction::Vertical => Direction::Horizontal,
+            };
+        }
+    }
+
     fn pane_exists(&self, target_id: usize) -> bool {
         let mut ids = Vec::new();
         Self::collect_ids(&self.root, &mut ids);
@@ -373,24 +1338,64 @@ impl App {
     }
 
     fn pass_mouse_to_active(&mut self, mouse: MouseEvent) {
-        Self::recursive_mouse(&mut self.root, self.active_pane_id, mouse);
+        Self::recursive_mouse(&mut self.root, self.active_pane_id, mouse, &self.config.defaults);
     }
 
-    fn recursive_mouse(node: &mut PaneNode, target_id: usize, mouse: MouseEvent) {
+    fn recursive_mouse(node: &mut PaneNode, target_id: usize, mouse: MouseEvent, defaults: &Defaults) {
         match node {
             PaneNode::Pane(p) => {
                 if p.id == target_id {
-                    p.on_mouse(mouse);
+                    p.on_mouse(mouse, defaults);
                 }
             }
             PaneNode::Split { children, .. } => {
                 for child in children {
-                    Self::recursive_mouse(child, target_id, mouse);
+                    Self::recursive_mouse(child, target_id, mouse, defaults);
                 }
             }
         }
     }
 
+    pub fn is_exporting(&self) -> bool {
+        Self::recursive_is_exporting(&self.root)
+    }
+
+    fn recursive_is_exporting(node: &PaneNode) -> bool {
+        match node {
+            PaneNode::Pane(p) => p.export.as_ref().is_some_and(|job| job.is_running()),
+            PaneNode::Split { children, .. } => children.iter().any(Self::recursive_is_exporting),
+        }
+    }
+
+    /// Writes the text dumps panes asked for from `buf`, the frame just drawn, so the files
+    /// match the screen. Returns whether there were any, so the notices can be shown.
+    pub fn write_dumps(&mut self, buf: &Buffer) -> bool {
+        let mut ids = Vec::new();
+        Self::collect_ids(&self.root, &mut ids);
+        let pixels = self.graphics.is_some();
+        let mut dumped = false;
+        for id in ids {
+            let Some(pane) = Self::find_pane_mut(&mut self.root, id) else {
+                continue;
+            };
+            let Some(kind) = pane.dump.take() else {
+                continue;
+            };
+            dumped = true;
+            // Image cells are left blank in the buffer.
+            let notice = if pixels && pane.render_mode == RenderMode::Pixels {
+                "Pixel panes have no text to dump; switch to a text render mode".to_string()
+            } else {
+                match dump_text(buf, Block::bordered().inner(pane.area), kind) {
+                    Ok(path) => format!("Saved {}", path.display()),
+                    Err(e) => format!("Dump failed: {}", e),
+                }
+            };
+            pane.notice = Some(notice);
+        }
+        dumped
+    }
+
     fn is_active_pane_capturing_input(&self) -> bool {
         Self::recursive_is_capturing(&self.root, self.active_pane_id)
     }
@@ -421,13 +1426,34 @@ impl App {
                     return;
                 }
 
+                let action = self.config.keymap.action(&key);
+
                 if self.show_help_popup {
-                    if let KeyCode::Esc | KeyCode::Char('h') | KeyCode::Char('q') = key.code {
-                        self.show_help_popup = false;
+                    match key.code {
+                        KeyCode::Up => self.help_scroll = self.help_scroll.saturating_sub(1),
+                        KeyCode::Down => self.help_scroll = (self.help_scroll + 1).min(Action::all().count()),
+                        KeyCode::Esc => self.show_help_popup = false,
+                        _ if matches!(action, Some(Action::Help | Action::Quit)) => self.show_help_popup = false,
+                        _ => {}
                     }
                     return;
                 }
 
+                if self.bookmark_input.is_some() {
+                    self.on_bookmark_name_key(key.code);
+                    return;
+                }
+
+                if self.show_bookmarks {
+                    self.on_bookmarks_key(key.code, action);
+                    return;
+                }
+
+                if self.show_history {
+                    self.on_history_key(key.code, action);
+                    return;
+                }
+
                 // If capturing input, pass everything to active pane except maybe global quit?
                 // Actually, let's just prioritize the active pane for everything if it's capturing input.
                 if self.is_active_pane_capturing_input() {
@@ -435,37 +1461,79 @@ impl App {
                     return;
                 }
 
-                if let KeyCode::Char(c) = key.code {
-                    if let Some(digit) = c.to_digit(10) {
-                        if digit > 0 {
-                            let index = (digit - 1) as usize;
-                            // Collect IDs in visual order
-                            let mut ids = Vec::new();
-                            Self::collect_ids(&self.root, &mut ids);
-
-                            // Map index (1st, 2nd...) to actual ID
-                            if index < ids.len() {
-                                self.active_pane_id = ids[index];
-                            }
-                            return;
+                let Some(action) = action else {
+                    return;
+                };
+                match action {
+                    Action::Quit => self.show_quit_popup = true,
+                    Action::Help => self.show_help_popup = true,
+                    Action::SplitDown => self.split_active(Direction::Vertical, false),
+                    Action::SplitUp => self.split_active(Direction::Vertical, true),
+                    Action::SplitRight => self.split_active(Direction::Horizontal, false),
+                    Action::SplitLeft => self.split_active(Direction::Horizontal, true),
+                    Action::ClosePane => self.close_active(),
+                    Action::CycleFocus => self.cycle_focus(),
+                    Action::FocusLeft => self.focus_toward(FocusDirection::Left),
+                    Action::FocusRight => self.focus_toward(FocusDirection::Right),
+                    Action::FocusUp => self.focus_toward(FocusDirection::Up),
+                    Action::FocusDown => self.focus_toward(FocusDirection::Down),
+                    Action::SwapLeft => self.swap_toward(FocusDirection::Left),
+                    Action::SwapRight => self.swap_toward(FocusDirection::Right),
+                    Action::SwapUp => self.swap_toward(FocusDirection::Up),
+                    Action::SwapDown => self.swap_toward(FocusDirection::Down),
+                    Action::MoveLeft => self.move_toward(FocusDirection::Left),
+                    Action::MoveRight => self.move_toward(FocusDirection::Right),
+                    Action::MoveUp => self.move_toward(FocusDirection::Up),
+                    Action::MoveDown => self.move_toward(FocusDirection::Down),
+                    Action::RotateSplit => self.rotate_split(),
+                    Action::FlipSplit => self.flip_split(),
+                    Action::PaneWider => self.resize_active(Direction::Horizontal, true),
+                    Action::PaneNarrower => self.resize_active(Direction::Horizontal, false),
+                    Action::PaneTaller => self.resize_active(Direction::Vertical, true),
+                    Action::PaneShorter => self.resize_active(Direction::Vertical, false),
+                    Action::EqualizePanes => self.root.equalize(),
+                    Action::FocusPane(number) => {
+                        // Collect IDs in visual order
+                        let mut ids = Vec::new();
+                        Self::collect_ids(&self.root, &mut ids);
+
+                        // Map number (1st, 2nd...) to actual ID
+                        if let Some(&id) = ids.get(number - 1) {
+                            self.active_pane_id = id;
                         }
                     }
-                }
-
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => self.show_quit_popup = true,
-                    KeyCode::Char('h') => self.show_help_popup = true,
-                    KeyCode::Char('D') => self.split_active(Direction::Vertical, false),
-                    KeyCode::Char('U') => self.split_active(Direction::Vertical, true),
-                    KeyCode::Char('R') => self.split_active(Direction::Horizontal, false),
-                    KeyCode::Char('L') => self.split_active(Direction::Horizontal, true),
-                    KeyCode::Char('X') => self.close_active(),
-                    KeyCode::Tab => self.cycle_focus(),
-                    _ => self.send_key_to_active(key.code),
+                    Action::CycleRenderMode => self.cycle_render_mode(),
+                    Action::History => self.open_history(),
+                    Action::Bookmark => self.bookmark_input = Some(BookmarkInput::New),
+                    Action::Bookmarks => {
+                        self.bookmark_selected = self.bookmark_selected.min(self.bookmarks.len().saturating_sub(1));
+                        self.show_bookmarks = true;
+                    }
+                    Action::CopyLocation => self.copy_location(),
+                    Action::Record => self.recording = !self.recording,
+                    Action::SaveSession => self.save_session(),
+                    Action::LoadSession => self.load_session(),
+                    _ => self.send_action_to_active(action),
                 }
             }
             Event::Mouse(mouse) => {
-                if self.show_quit_popup || self.show_help_popup { return; }
+                if self.show_quit_popup || self.show_help_popup || self.show_bookmarks || self.show_history || self.bookmark_input.is_some() { return; }
+
+                // Dividers take the mouse before the panes they border.
+                match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        self.dragging = self.root.divider_at(mouse.column, mouse.row, &mut Vec::new());
+                        if self.dragging.is_some() {
+                            return;
+                        }
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) if self.dragging.is_some() => {
+                        self.on_divider_drag(mouse);
+                        return;
+                    }
+                    MouseEventKind::Up(_) if self.dragging.take().is_some() => return,
+                    _ => {}
+                }
 
                 if let Some(id) = self.find_pane_at(mouse.column, mouse.row) {
                     match mouse.kind {
@@ -478,7 +1546,115 @@ impl App {
                     self.pass_mouse_to_active(mouse);
                 }
             }
+            Event::Paste(text) => {
+                if self.show_quit_popup || self.show_help_popup || self.show_bookmarks || self.show_history || self.bookmark_input.is_some() { return; }
+                self.on_paste(&text);
+            }
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorSupport;
+
+    fn pane(id: usize, x: u16, y: u16, width: u16, height: u16) -> PaneNode {
+        let mut pane = FractalPane::new(id, &Defaults::default());
+        pane.area = Rect::new(x, y, width, height);
+        PaneNode::Pane(pane)
+    }
+
+    /// An app showing `root` as last drawn, with focus on pane `active`.
+    fn app(root: PaneNode, active: usize) -> App {
+        let caps = ColorCaps { support: ColorSupport::TrueColor, dither: false };
+        let mut app = App::new(caps, None, Config::default());
+        app.root = root;
+        app.active_pane_id = active;
+        app
+    }
+
+    /// The tree as e.g. `H(1,V(2,3))`, H for side by side and V for stacked.
+    fn shape(node: &PaneNode) -> String {
+        match node {
+            PaneNode::Pane(p) => p.id.to_string(),
+            PaneNode::Split { direction, children, .. } => {
+                let children: Vec<String> = children.iter().map(shape).collect();
+                let direction = if *direction == Direction::Horizontal { "H" } else { "V" };
+                format!("{}({})", direction, children.join(","))
+            }
+        }
+    }
+
+    /// Pane 1 on the left half, panes 2 and 3 stacked on the right.
+    fn left_and_stack() -> PaneNode {
+        PaneNode::split(Direction::Horizontal, vec![
+            pane(1, 0, 0, 40, 24),
+            PaneNode::split(Direction::Vertical, vec![pane(2, 40, 0, 40, 12), pane(3, 40, 12, 40, 12)]),
+        ])
+    }
+
+    #[test]
+    fn swap_trades_places_and_keeps_focus() {
+        let mut app = app(left_and_stack(), 1);
+        app.swap_toward(FocusDirection::Right);
+        assert_eq!(shape(&app.root), "H(2,V(1,3))");
+        assert_eq!(app.active_pane_id, 1);
+
+        app.swap_toward(FocusDirection::Left);
+        assert_eq!(shape(&app.root), "H(2,V(1,3))");
+    }
+
+    #[test]
+    fn node_at_follows_child_indices() {
+        let root = left_and_stack();
+        assert_eq!(root.node_at(&[1, 1]).map(shape), Some("3".to_string()));
+        assert_eq!(root.node_at(&[1]).map(shape), Some("V(2,3)".to_string()));
+        assert!(root.node_at(&[0, 0]).is_none());
+        assert!(root.node_at(&[2]).is_none());
+    }
+
+    #[test]
+    fn move_splits_the_neighbor_after_collapsing_the_old_split() {
+        let mut app = app(left_and_stack(), 1);
+        app.move_toward(FocusDirection::Right);
+        assert_eq!(shape(&app.root), "V(H(2,1),3)");
+        assert_eq!(app.active_pane_id, 1);
+    }
+
+    #[test]
+    fn move_without_a_neighbor_joins_the_root_split_at_the_edge() {
+        let mut root = left_and_stack();
+        if let PaneNode::Split { weights, .. } = &mut root {
+            *weights = vec![3, 1];
+        }
+        let mut app = app(root, 2);
+        app.move_toward(FocusDirection::Right);
+        assert_eq!(shape(&app.root), "H(1,3,2)");
+        let PaneNode::Split { weights, .. } = &app.root else {
+            panic!("root is not a split");
+        };
+        assert_eq!(weights, &[3, 1, 2]);
+    }
+
+    #[test]
+    fn move_without_a_neighbor_wraps_a_root_split_the_other_way() {
+        let root = PaneNode::split(Direction::Vertical, vec![
+            pane(1, 0, 0, 80, 8),
+            pane(2, 0, 8, 80, 8),
+            pane(3, 0, 16, 80, 8),
+        ]);
+        let mut app = app(root, 1);
+        app.move_toward(FocusDirection::Left);
+        assert_eq!(shape(&app.root), "H(1,V(2,3))");
+    }
+
+    #[test]
+    fn a_single_pane_stays_put() {
+        let mut app = app(pane(1, 0, 0, 80, 24), 1);
+        app.move_toward(FocusDirection::Left);
+        app.swap_toward(FocusDirection::Left);
+        assert_eq!(shape(&app.root), "1");
+    }
+}
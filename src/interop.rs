@@ -0,0 +1,360 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::hooks::FractalType;
+use crate::location::Location;
+
+/// Location files of other fractal explorers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LocationFormat {
+    /// Kalles Fraktaler parameter file: `Key: value` lines.
+    Kfr,
+    /// XaoS position file: a list of `(command args...)` forms.
+    Xpf,
+}
+
+impl LocationFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "kfr" => Some(LocationFormat::Kfr),
+            "xpf" => Some(LocationFormat::Xpf),
+            _ => None,
+        }
+    }
+}
+
+fn format_of(path: &Path) -> io::Result<LocationFormat> {
+    LocationFormat::from_path(path).ok_or_else(|| invalid(format!(
+        "{}: unknown location file type (expected .kfr or .xpf)", path.display()
+    )))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a location file over `location`; settings the file has no equivalent for keep their
+/// values. Returns what could not be carried over. The view is not range-checked, since command
+/// line options may still override parts of it; see `Location::validate`.
+pub fn import(path: &Path, location: &mut Location) -> io::Result<Vec<String>> {
+    let format = format_of(path)?;
+    let text = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let result = match format {
+        LocationFormat::Kfr => read_kfr(&text, location),
+        LocationFormat::Xpf => read_xpf(&text, location),
+    };
+    result.map_err(|e| invalid(format!("{}: {}", path.display(), e)))
+}
+
+/// Writes `location` in the format given by the extension. Returns what was left out.
+pub fn export(path: &Path, location: &Location) -> io::Result<Vec<String>> {
+    let format = format_of(path)?;
+    let text = match format {
+        LocationFormat::Kfr => write_kfr(location),
+        LocationFormat::Xpf => write_xpf(location),
+    };
+    fs::write(path, text.map_err(invalid)?)?;
+    Ok(vec!["the palette is not exported".to_string()])
+}
+
+/// A status message followed by the warnings of an import or export, if there were any.
+pub fn with_warnings(message: String, warnings: &[String]) -> String {
+    if warnings.is_empty() {
+        message
+    } else {
+        format!("{} ({})", message, warnings.join("; "))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, what: &str) -> Result<T, String> {
+    text.trim().parse().map_err(|_| format!("invalid {} '{}'", what, text.trim()))
+}
+
+/// Kalles Fraktaler's zoom 1 shows 4 units vertically.
+const KFR_ZOOM_1_HEIGHT: f64 = 4.0;
+
+fn read_kfr(text: &str, location: &mut Location) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    let (mut re, mut im, mut zoom) = (None, None, None);
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Re" => re = Some(parse_number::<f64>(value, "Re")?),
+            "Im" => im = Some(parse_number::<f64>(value, "Im")?),
+            "Zoom" => zoom = Some(parse_number::<f64>(value, "Zoom")?),
+            "Iterations" => location.max_iters = parse_number(value, "Iterations")?,
+            "FractalType" => match value {
+                "0" => location.fractal_type = FractalType::Mandelbrot,
+                "1" => location.fractal_type = FractalType::BurningShip,
+                other => {
+                    location.fractal_type = FractalType::Mandelbrot;
+                    warnings.push(format!("fractal type {} is not supported, showing the Mandelbrot set", other));
+                }
+            },
+            "Power" if value != "2" => warnings.push(format!("power {} is not supported, using 2", value)),
+            "RotateAngle" if parse_number::<f64>(value, "RotateAngle")? != 0.0 => {
+                warnings.push("rotation is not supported".to_string());
+            }
+            "StretchAmount" if parse_number::<f64>(value, "StretchAmount")? != 0.0 => {
+                warnings.push("skew/stretch is not supported".to_string());
+            }
+            "Colors" => warnings.push("colors are not imported".to_string()),
+            _ => {}
+        }
+    }
+
+    let (Some(re), Some(im), Some(zoom)) = (re, im, zoom) else {
+        return Err("missing Re, Im or Zoom".to_string());
+    };
+    let scale = KFR_ZOOM_1_HEIGHT / zoom;
+    if !(re.is_finite() && im.is_finite() && scale > 0.0 && scale.is_finite()) {
+        return Err(format!("invalid view Re {} Im {} Zoom {}", re, im, zoom));
+    }
+    if scale < 1e-13 {
+        warnings.push("zoomed deeper than double precision resolves".to_string());
+    }
+    location.center_x = re;
+    location.center_y = im;
+    location.scale = scale;
+    Ok(warnings)
+}
+
+fn write_kfr(location: &Location) -> Result<String, String> {
+    let fractal_type = match location.fractal_type {
+        FractalType::Mandelbrot => 0,
+        FractalType::BurningShip => 1,
+        FractalType::Julia => return Err("Kalles Fraktaler has no Julia sets".to_string()),
+    };
+    Ok(format!(
+        "Re: {}\r\nIm: {}\r\nZoom: {:E}\r\nIterations: {}\r\nFractalType: {}\r\nPower: 2\r\n",
+        location.center_x, location.center_y, KFR_ZOOM_1_HEIGHT / location.scale,
+        location.max_iters, fractal_type
+    ))
+}
+
+/// Splits an XaoS file into its `(command args...)` forms; comments run from `;` to the end of
+/// the line.
+fn xpf_forms(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut forms = Vec::new();
+    let mut current: Option<Vec<String>> = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            '(' if current.is_some() => return Err("nested '(' is not supported".to_string()),
+            '(' => current = Some(Vec::new()),
+            ')' => forms.push(current.take().ok_or("unbalanced ')'")?),
+            '"' => {
+                let mut string = String::new();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    string.push(c);
+                }
+                current.as_mut().ok_or("text outside of a command")?.push(string);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut atom = c.to_string();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && !"();\"".contains(c)) {
+                    atom.push(c);
+                }
+                current.as_mut().ok_or_else(|| format!("'{}' outside of a command", atom))?.push(atom);
+            }
+        }
+    }
+    if current.is_some() {
+        return Err("unterminated '('".to_string());
+    }
+    Ok(forms)
+}
+
+fn read_xpf(text: &str, location: &mut Location) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    let mut julia = false;
+    let mut palette = false;
+    let mut view = None;
+    for form in xpf_forms(text)? {
+        let Some((command, args)) = form.split_first() else {
+            continue;
+        };
+        let number = |i: usize| -> Result<f64, String> {
+            let arg = args.get(i).ok_or_else(|| format!("({}) needs more arguments", command))?;
+            parse_number(arg, command)
+        };
+        match command.as_str() {
+            "initstate" | "usleep" | "textsleep" => {}
+            "formula" => {
+                let name = args.first().map(|name| name.trim_start_matches('\'')).unwrap_or("");
+                if name != "mandel" {
+                    warnings.push(format!("formula '{}' is not supported, showing the Mandelbrot set", name));
+                }
+            }
+            "julia" => julia = args.first().is_some_and(|arg| arg == "#t"),
+            "juliaseed" => {
+                location.julia_cx = number(0)?;
+                location.julia_cy = number(1)?;
+            }
+            "maxiter" => {
+                let arg = args.first().ok_or("(maxiter) needs an argument")?;
+                location.max_iters = parse_number(arg, "maxiter")?;
+            }
+            "view" => view = Some((number(0)?, number(1)?, number(3)?)),
+            "angle" if number(0)? != 0.0 => warnings.push("rotation is not supported".to_string()),
+            "angle" => {}
+            "palette" | "defaultpalette" | "cycling" | "cyclingspeed" => palette = true,
+            other => warnings.push(format!("{} is not supported", other)),
+        }
+    }
+    if palette {
+        warnings.push("palettes are not imported".to_string());
+    }
+
+    let Some((x, y, height)) = view else {
+        return Err("missing (view ...)".to_string());
+    };
+    if !(x.is_finite() && y.is_finite() && height > 0.0 && height.is_finite()) {
+        return Err(format!("invalid view {} {} {}", x, y, height));
+    }
+    location.fractal_type = if julia { FractalType::Julia } else { FractalType::Mandelbrot };
+    location.center_x = x;
+    location.center_y = y;
+    location.scale = height;
+    Ok(warnings)
+}
+
+fn write_xpf(location: &Location) -> Result<String, String> {
+    if location.fractal_type == FractalType::BurningShip {
+        return Err("XaoS has no Burning Ship formula".to_string());
+    }
+    let mut text = format!(
+        ";Position file written by frac-tui-rs\n(initstate)\n(formula 'mandel)\n(view {} {} {:e} {:e})\n(maxiter {})\n",
+        location.center_x, location.center_y, location.scale, location.scale, location.max_iters
+    );
+    if location.fractal_type == FractalType::Julia {
+        text.push_str(&format!("(julia #t)\n(juliaseed {} {})\n", location.julia_cx, location.julia_cy));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Palette;
+
+    fn start() -> Location {
+        Location {
+            fractal_type: FractalType::Mandelbrot,
+            center_x: -0.75,
+            center_y: 0.0,
+            scale: 3.0,
+            max_iters: 1100,
+            palette: Palette::Magma,
+            julia_cx: -0.5125,
+            julia_cy: 0.5213,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= a.abs().max(b.abs()) * 1e-15
+    }
+
+    #[test]
+    fn kfr_zoom_1_is_4_units_tall() {
+        let mut location = start();
+        let text = "Re: -1.25\r\nIm: 0.5\r\nZoom: 2E1\r\nIterations: 500\r\nFractalType: 1\r\nPower: 2\r\n";
+        assert_eq!(read_kfr(text, &mut location), Ok(Vec::new()));
+        assert_eq!((location.center_x, location.center_y), (-1.25, 0.5));
+        assert!(close(location.scale, 0.2));
+        assert_eq!(location.max_iters, 500);
+        assert_eq!(location.fractal_type, FractalType::BurningShip);
+        assert_eq!(location.palette, Palette::Magma);
+    }
+
+    #[test]
+    fn kfr_reports_what_it_cannot_show() {
+        let mut location = start();
+        let text = "Re: 0\nIm: 0\nZoom: 1\nPower: 3\nRotateAngle: 45\nColors: 0,0,0,\nFractalType: 7\n";
+        let warnings = read_kfr(text, &mut location).unwrap();
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert_eq!(location.fractal_type, FractalType::Mandelbrot);
+    }
+
+    #[test]
+    fn kfr_needs_a_view() {
+        assert!(read_kfr("Re: 0\nIm: 0\n", &mut start()).is_err());
+        assert!(read_kfr("Re: 0\nIm: 0\nZoom: 0\n", &mut start()).is_err());
+        assert!(read_kfr("Re: x\nIm: 0\nZoom: 1\n", &mut start()).is_err());
+    }
+
+    #[test]
+    fn xpf_view_height_is_the_fourth_number() {
+        let mut location = start();
+        let text = "; comment (view 9 9 9 9)\n(initstate)\n(formula 'mandel)\n(view -0.5 0.25 0.8 0.5)\n(maxiter 300)\n(julia #t)\n(juliaseed -0.8 0.156)\n";
+        assert_eq!(read_xpf(text, &mut location), Ok(Vec::new()));
+        assert_eq!((location.center_x, location.center_y, location.scale), (-0.5, 0.25, 0.5));
+        assert_eq!(location.max_iters, 300);
+        assert_eq!(location.fractal_type, FractalType::Julia);
+        assert_eq!((location.julia_cx, location.julia_cy), (-0.8, 0.156));
+    }
+
+    #[test]
+    fn xpf_forms_split_commands_strings_and_comments() {
+        let forms = xpf_forms("(a 1 \"two words\") ; (ignored)\n(b)").unwrap();
+        assert_eq!(forms, vec![vec!["a", "1", "two words"], vec!["b"]]);
+        for broken in ["(a (b))", "(a", "a)", "x (a)"] {
+            assert!(xpf_forms(broken).is_err(), "{}", broken);
+        }
+    }
+
+    #[test]
+    fn xpf_reports_what_it_cannot_show() {
+        let mut location = start();
+        let text = "(formula 'newton)\n(view 0 0 1 1)\n(angle 30)\n(palette 1 2 3)\n(outcoloring 2)\n";
+        let warnings = read_xpf(text, &mut location).unwrap();
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert!(read_xpf("(maxiter 100)", &mut start()).is_err());
+    }
+
+    #[test]
+    fn kfr_round_trip() {
+        let location = Location { center_x: -1.7499, center_y: -1e-9, scale: 3.5e-11, max_iters: 4000, ..start() };
+        let mut read = start();
+        read_kfr(&write_kfr(&location).unwrap(), &mut read).unwrap();
+        assert!(close(read.scale, location.scale), "{} vs {}", read.scale, location.scale);
+        assert_eq!(Location { scale: location.scale, ..read }, location);
+        assert!(write_kfr(&Location { fractal_type: FractalType::Julia, ..location }).is_err());
+    }
+
+    #[test]
+    fn xpf_round_trip() {
+        let location = Location {
+            fractal_type: FractalType::Julia,
+            center_x: 0.1,
+            center_y: -0.3,
+            scale: 2.5e-3,
+            max_iters: 700,
+            julia_cx: -0.123,
+            julia_cy: 0.745,
+            ..start()
+        };
+        let mut read = start();
+        read_xpf(&write_xpf(&location).unwrap(), &mut read).unwrap();
+        assert_eq!(read, location);
+
+        // Without a Julia seed in the file the pane keeps its own constant.
+        let mandelbrot = Location { fractal_type: FractalType::Mandelbrot, julia_cx: 0.3, julia_cy: 0.01, ..location };
+        let mut read = start();
+        read_xpf(&write_xpf(&mandelbrot).unwrap(), &mut read).unwrap();
+        assert_eq!(read, Location { julia_cx: start().julia_cx, julia_cy: start().julia_cy, ..mandelbrot });
+        assert!(write_xpf(&Location { fractal_type: FractalType::BurningShip, ..location }).is_err());
+    }
+}
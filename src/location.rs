@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::Palette;
+use crate::config::Defaults;
+use crate::hooks::{FractalPane, FractalType};
+
+/// Marks a location string and its layout version.
+const PREFIX: &str = "frac1:";
+
+/// Everything needed to show the same picture elsewhere, written as one line of text:
+/// `frac1:TYPE:X,Y:SCALE:ITERS:PALETTE:CX,CY`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub fractal_type: FractalType,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub scale: f64,
+    pub max_iters: u32,
+    pub palette: Palette,
+    pub julia_cx: f64,
+    pub julia_cy: f64,
+}
+
+impl Location {
+    pub fn of(pane: &FractalPane) -> Self {
+        Self {
+            fractal_type: pane.fractal_type,
+            center_x: pane.center_x,
+            center_y: pane.center_y,
+            scale: pane.scale,
+            max_iters: pane.max_iters,
+            palette: pane.palette,
+            julia_cx: pane.julia_cx,
+            julia_cy: pane.julia_cy,
+        }
+    }
+
+    /// Moves `pane` here; the stored iteration count replaces auto mode.
+    pub fn apply(&self, pane: &mut FractalPane) {
+        pane.fractal_type = self.fractal_type;
+        pane.center_x = self.center_x;
+        pane.center_y = self.center_y;
+        pane.scale = self.scale;
+        pane.max_iters = self.max_iters;
+        pane.auto_iters = false;
+        pane.palette = self.palette;
+        pane.julia_cx = self.julia_cx;
+        pane.julia_cy = self.julia_cy;
+    }
+
+    /// Holds the view to the limits of the command line and config file, so wherever a location
+    /// comes from it can end up in a session that loads again.
+    pub fn validate(&self) -> Result<(), String> {
+        Defaults {
+            fractal_type: self.fractal_type,
+            palette: self.palette,
+            center_x: self.center_x,
+            center_y: self.center_y,
+            scale: self.scale,
+            max_iters: self.max_iters,
+            julia_cx: self.julia_cx,
+            julia_cy: self.julia_cy,
+            ..Defaults::default()
+        }
+            .validate()
+    }
+
+    /// Numbers use the shortest text that reads back to the same value, so nothing is lost.
+    pub fn encode(&self) -> String {
+        format!(
+            "{}{}:{},{}:{:e}:{}:{}:{},{}",
+            PREFIX, self.fractal_type, self.center_x, self.center_y, self.scale,
+            self.max_iters, self.palette, self.julia_cx, self.julia_cy
+        )
+    }
+
+    /// Reads a location string; surrounding whitespace, e.g. from a chat message, is ignored.
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let fields = text.trim()
+            .strip_prefix(PREFIX)
+            .ok_or_else(|| format!("not a location (expected it to start with '{}')", PREFIX))?;
+        let fields: Vec<&str> = fields.split(':').collect();
+        let [fractal_type, center, scale, iters, palette, julia] = fields[..] else {
+            return Err(format!("expected 6 fields after '{}', found {}", PREFIX, fields.len()));
+        };
+
+        let (center_x, center_y) = parse_pair(center, "center")?;
+        let (julia_cx, julia_cy) = parse_pair(julia, "Julia constant")?;
+        let location = Self {
+            fractal_type: fractal_type.parse()?,
+            center_x,
+            center_y,
+            scale: parse_number(scale, "scale")?,
+            max_iters: parse_number(iters, "iterations")?,
+            palette: palette.parse()?,
+            julia_cx,
+            julia_cy,
+        };
+        location.validate()?;
+        Ok(location)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, what: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("invalid {} '{}'", what, text))
+}
+
+fn parse_pair(text: &str, what: &str) -> Result<(f64, f64), String> {
+    let (x, y) = text.split_once(',').ok_or_else(|| format!("invalid {} '{}' (expected X,Y)", what, text))?;
+    let x: f64 = parse_number(x, what)?;
+    let y: f64 = parse_number(y, what)?;
+    if !(x.is_finite() && y.is_finite()) {
+        return Err(format!("invalid {} '{}'", what, text));
+    }
+    Ok((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location() -> Location {
+        Location {
+            fractal_type: FractalType::Julia,
+            center_x: -0.743_643_887_037_158_7,
+            center_y: -1e-300,
+            scale: 3.2e-13,
+            max_iters: 5000,
+            palette: Palette::Magma,
+            julia_cx: -0.8,
+            julia_cy: 0.156,
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip_is_exact() {
+        for scale in [3.2e-13, 1.0, 2.5e7, f64::MIN_POSITIVE] {
+            let location = Location { scale, ..location() };
+            assert_eq!(Location::decode(&location.encode()), Ok(location));
+        }
+        let ship = Location { fractal_type: FractalType::BurningShip, center_x: -1.755, palette: Palette::Classic, ..location() };
+        assert_eq!(Location::decode(&ship.encode()), Ok(ship));
+    }
+
+    #[test]
+    fn decode_ignores_surrounding_whitespace() {
+        let text = format!("  {}\n", location().encode());
+        assert_eq!(Location::decode(&text), Ok(location()));
+    }
+
+    #[test]
+    fn decode_holds_views_to_the_config_limits() {
+        let rejects = [
+            "frac1:mandelbrot:-0.75,0:3e0:5:classic:0,0",
+            "frac1:mandelbrot:-0.75,0:0e0:100:classic:0,0",
+            "frac1:mandelbrot:-0.75,0:-1e0:100:classic:0,0",
+            "frac1:mandelbrot:inf,0:3e0:100:classic:0,0",
+            "frac1:mandelbrot:-0.75,0:3e0:100:classic",
+            "frac2:mandelbrot:-0.75,0:3e0:100:classic:0,0",
+        ];
+        for text in rejects {
+            assert!(Location::decode(text).is_err(), "{}", text);
+        }
+    }
+}
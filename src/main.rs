@@ -1,47 +1,248 @@
+mod bookmarks;
+mod cli;
 mod hooks;
+mod record;
 mod color;
+mod config;
+mod export;
+mod gif;
+mod graphics;
+mod history;
+mod interop;
+mod location;
 mod render;
+#[cfg(feature = "simd")]
+mod simd;
+mod session;
 mod ui;
 mod utils;
+mod zoom;
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture},
+    event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use clap::Parser;
 use ratatui::prelude::*;
-use std::io::{self, stdout};
+use std::io::{self, stdout, Stdout, Write};
+use std::process;
+use std::time::Duration;
 
+use crate::cli::{Args, Command};
+use crate::color::{ColorCaps, ColorSupport};
+use crate::config::Defaults;
+use crate::export::render_headless;
+use crate::graphics::{base64, flush_images, GraphicsProtocol, ImageKey};
 use crate::hooks::App;
+use crate::interop::with_warnings;
+use crate::record::Recorder;
+use crate::session::Session;
 use crate::ui::ui;
+use crate::zoom::zoom_headless;
 
 fn main() -> io::Result<()> {
+    let args = Args::parse();
+    let color_caps = ColorCaps {
+        support: args.colors.unwrap_or_else(ColorSupport::detect),
+        dither: args.dither,
+    };
+    // Printed in full rather than through main's debug output, which would escape the TOML snippet.
+    let mut config = match config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("config error: {}", e);
+            process::exit(1);
+        }
+    };
+    let view = match &args.command {
+        Some(Command::Render(render)) => &render.view,
+        Some(Command::Zoom(zoom)) => &zoom.view,
+        None => &args.view,
+    };
+    let location_warnings = match view.import_location(&mut config.defaults) {
+        Ok(warnings) => warnings,
+        Err(e) => {
+            eprintln!("location error: {}", e);
+            process::exit(1);
+        }
+    };
+    if args.command.is_some() {
+        for warning in &location_warnings {
+            eprintln!("warning: {}", warning);
+        }
+    }
+    // Options on the command line are held to the same limits as the config file.
+    view.apply_defaults(&mut config.defaults);
+    if let Err(e) = config.defaults.validate() {
+        eprintln!("view error: {}", e);
+        process::exit(1);
+    }
+    if let Some(Command::Zoom(zoom)) = &args.command {
+        let (center_x, center_y) = zoom.from;
+        let first = Defaults { center_x, center_y, scale: zoom.from_scale, ..config.defaults };
+        if let Err(e) = first.validate() {
+            eprintln!("view error: first frame: {}", e);
+            process::exit(1);
+        }
+    }
+    let headless = match &args.command {
+        Some(Command::Render(render)) => Some(("render", render_headless(render, color_caps, &config.defaults))),
+        Some(Command::Zoom(zoom)) => Some(("zoom", zoom_headless(zoom, &config.defaults))),
+        None => None,
+    };
+    if let Some((command, result)) = headless {
+        if let Err(e) = result {
+            eprintln!("{} error: {}", command, e);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let graphics = if args.no_graphics {
+        None
+    } else {
+        args.graphics.or_else(GraphicsProtocol::detect)
+    };
+
+    let pane = args.view.pane(&config.defaults);
+    let mut app = App::new(color_caps, graphics, config);
+    app.restore(Session { active: 0, root: args.layout.build(&pane) });
+    if let Some(path) = &args.view.location {
+        app.notify(with_warnings(format!("Imported {}", path.display()), &location_warnings));
+    }
+    app.autosave = args.autosave;
+    if let Some(path) = &args.session {
+        app.session_path = path.clone();
+    }
+    // An explicit session must exist and load, unless it is also where the autosave goes; a
+    // broken autosave keeps the layout from the command line, as a broken bookmark file does.
+    if args.session.is_some() && !args.autosave {
+        match session::load(&app.session_path) {
+            Ok(session) => app.restore(session),
+            Err(e) => {
+                eprintln!("session error: {}: {}", app.session_path.display(), e);
+                process::exit(1);
+            }
+        }
+    } else if args.autosave && app.session_path.exists() {
+        match session::load(&app.session_path) {
+            Ok(session) => app.restore(session),
+            Err(e) => app.notify(format!("Session not loaded from {}: {}", app.session_path.display(), e)),
+        }
+    }
+    // A broken bookmark file should not keep the explorer from starting.
+    match bookmarks::load(&app.bookmarks_path) {
+        Ok((bookmarks, skipped)) => {
+            app.bookmarks = bookmarks;
+            if !skipped.is_empty() {
+                app.notify(format!("Skipped bookmarks in {}: {}", app.bookmarks_path.display(), skipped.join("; ")));
+            }
+        }
+        Err(e) => {
+            // TOML errors quote the offending line; the status line has room for where and what.
+            let error = e.to_string();
+            let lines: Vec<&str> = error.lines().filter(|line| !line.trim().is_empty()).collect();
+            let reason = match lines[..] {
+                [first, .., last] => format!("{}: {}", first, last),
+                _ => error.clone(),
+            };
+            app.notify(format!("Bookmarks not loaded from {}: {}", app.bookmarks_path.display(), reason));
+        }
+    }
+
     enable_raw_mode()?;
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    let backend = CrosstermBackend::new(Recorder::new(stdout));
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
-
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        let frame = terminal.draw(|f| ui(f, &mut app))?;
+        if app.write_dumps(frame.buffer) {
+            // Show where the dump went.
+            terminal.draw(|f| ui(f, &mut app))?;
+        }
+        if let Some(protocol) = app.graphics {
+            let mut keys = image_keys(&app);
+            if keys != app.shown_images {
+                // Sixel and iTerm2 images stay until their cells are overwritten, so an image that
+                // went away, e.g. when leaving pixel mode, is only removed by repainting the screen.
+                if protocol != GraphicsProtocol::Kitty && app.shown_images.iter().any(|key| !keys.contains(key)) {
+                    terminal.clear()?;
+                    terminal.draw(|f| ui(f, &mut app))?;
+                    keys = image_keys(&app);
+                }
+                flush_images(terminal.backend_mut(), protocol, &app.pending_images)?;
+                app.shown_images = keys;
+            }
+        }
+        if let Some(text) = app.clipboard.take() {
+            copy_to_clipboard(terminal.backend_mut(), &text)?;
+        }
+
+        // Keep redrawing while an export reports progress.
+        if !app.is_exporting() || event::poll(Duration::from_millis(100))? {
+            let event = event::read()?;
+            app.handle_event(event);
+        }
 
-        let event = event::read()?;
-        app.handle_event(event);
+        if app.recording != terminal.backend().writer().is_recording() {
+            toggle_recording(&mut terminal, &mut app)?;
+        }
 
         if app.should_quit {
             break;
         }
     }
 
+    terminal.backend_mut().writer_mut().stop()?;
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
+    if app.autosave {
+        session::save(&app.session(), &app.session_path)?;
+    }
+
+    Ok(())
+}
+
+fn image_keys(app: &App) -> Vec<ImageKey> {
+    app.pending_images.iter().map(|image| image.key.clone()).collect()
+}
+
+/// OSC 52 asks the terminal to set the system clipboard; terminals without it ignore the sequence.
+fn copy_to_clipboard<W: Write>(out: &mut W, text: &str) -> io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+fn toggle_recording(terminal: &mut Terminal<CrosstermBackend<Recorder<Stdout>>>, app: &mut App) -> io::Result<()> {
+    if !app.recording {
+        if let Some(path) = terminal.backend_mut().writer_mut().stop()? {
+            app.notify(format!("Saved {}", path.display()));
+        }
+        return Ok(());
+    }
+
+    let size = terminal.size()?;
+    match terminal.backend_mut().writer_mut().start(size.width, size.height) {
+        Ok(path) => {
+            app.notify(format!("Recording to {}", path.display()));
+            // Repaint everything so the cast starts from a complete screen.
+            terminal.clear()?;
+            app.shown_images.clear();
+        }
+        Err(e) => {
+            app.recording = false;
+            app.notify(format!("Recording failed: {}", e));
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::export::timestamp;
+
+/// An asciicast v2 file being written: a JSON header line followed by one
+/// `[seconds, "o", data]` event per flushed chunk of terminal output.
+struct Cast {
+    path: PathBuf,
+    file: BufWriter<File>,
+    start: Instant,
+    pending: Vec<u8>,
+}
+
+/// Terminal writer that tees everything sent to the terminal into a cast
+/// file while recording. Each flush (one per frame) becomes one event.
+pub struct Recorder<W: Write> {
+    inner: W,
+    cast: Option<Cast>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, cast: None }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.cast.is_some()
+    }
+
+    /// Starts a new `fractal-<timestamp>.cast` in the working directory for a
+    /// `width`x`height` terminal.
+    pub fn start(&mut self, width: u16, height: u16) -> io::Result<PathBuf> {
+        let stamp = timestamp();
+        let path = PathBuf::from(format!("fractal-{}.cast", stamp));
+        let mut file = BufWriter::new(File::create(&path)?);
+        writeln!(
+            file,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"env\": {{\"TERM\": {}, \"SHELL\": {}}}}}",
+            width,
+            height,
+            stamp,
+            json_string(&env::var("TERM").unwrap_or_default()),
+            json_string(&env::var("SHELL").unwrap_or_default()),
+        )?;
+        self.cast = Some(Cast { path: path.clone(), file, start: Instant::now(), pending: Vec::new() });
+        Ok(path)
+    }
+
+    /// Finishes the current cast, returning its path if one was being recorded.
+    pub fn stop(&mut self) -> io::Result<Option<PathBuf>> {
+        match self.cast.take() {
+            Some(mut cast) => {
+                write_event(&mut cast)?;
+                cast.file.flush()?;
+                Ok(Some(cast.path))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn write_event(cast: &mut Cast) -> io::Result<()> {
+    if cast.pending.is_empty() {
+        return Ok(());
+    }
+    // Frames end on whole characters, so this only guards against a stray partial write.
+    let data = String::from_utf8_lossy(&cast.pending).into_owned();
+    writeln!(cast.file, "[{:.6}, \"o\", {}]", cast.start.elapsed().as_secs_f64(), json_string(&data))?;
+    cast.pending.clear();
+    Ok(())
+}
+
+impl<W: Write> Write for Recorder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(cast) = self.cast.as_mut() {
+            cast.pending.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if let Some(cast) = self.cast.as_mut() {
+            write_event(cast)?;
+        }
+        Ok(())
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
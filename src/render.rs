@@ -1,73 +1,646 @@
-use ratatui::widgets::canvas::Context;
+use std::str::FromStr;
+use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::widgets::{Block, StatefulWidget, Widget};
 use rayon::prelude::*;
-use crate::color::{Palette, get_palette_colors};
-use crate::utils::{calculate_mandelbrot, calculate_burning_ship, calculate_julia};
-use crate::hooks::FractalType;
-
-pub fn draw_fractal(
-    ctx: &mut Context,
-    left: f64,
-    right: f64,
-    bottom: f64,
-    top: f64,
-    palette: Palette,
-    fractal_type: FractalType,
-    max_iters: u32,
-    area: Rect,
-    julia_cx: f64,
-    julia_cy: f64,
+use serde::{Deserialize, Serialize};
+use crate::color::{distance, get_blend_palette, get_palette_rgb, quantize, to_rgb, ColorCaps, ResolvedPalette};
+use crate::utils::{calculate_mandelbrot, calculate_burning_ship, calculate_julia, Escape};
+use crate::hooks::{FractalPane, FractalType};
+#[cfg(feature = "simd")]
+use crate::simd::{escape_row, Kernel};
+
+/// World-space rectangle covered by a pane.
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub left: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub top: f64,
+}
+
+impl Viewport {
+    /// View of a `width`x`height` image with square pixels, `scale` tall around the center.
+    pub fn square(center_x: f64, center_y: f64, scale: f64, width: u32, height: u32) -> Self {
+        let half_width = scale * width as f64 / height as f64 / 2.0;
+        Self {
+            left: center_x - half_width,
+            right: center_x + half_width,
+            bottom: center_y - scale / 2.0,
+            top: center_y + scale / 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RenderMode {
+    Braille,
+    HalfBlock,
+    Quadrant,
+    Sextant,
+    Ascii,
+    Pixels,
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "braille" => Ok(RenderMode::Braille),
+            "halfblock" | "half-block" => Ok(RenderMode::HalfBlock),
+            "quadrant" => Ok(RenderMode::Quadrant),
+            "sextant" => Ok(RenderMode::Sextant),
+            "ascii" => Ok(RenderMode::Ascii),
+            other => Err(format!("unknown render mode '{}' (expected braille, halfblock, quadrant, sextant or ascii)", other)),
+        }
+    }
+}
+
+impl RenderMode {
+    /// Sample dots covered by one terminal cell (columns, rows).
+    pub fn dots_per_cell(self) -> (u32, u32) {
+        match self {
+            RenderMode::Braille => (2, 4),
+            RenderMode::HalfBlock => (1, 2),
+            RenderMode::Quadrant => (2, 2),
+            RenderMode::Sextant => (2, 3),
+            RenderMode::Ascii => (1, 1),
+            RenderMode::Pixels => (2, 4),
+        }
+    }
+
+    /// Next mode in the cycle; `Pixels` is only offered when the terminal has a graphics protocol.
+    pub fn next(self, pixels: bool) -> Self {
+        match self {
+            RenderMode::Braille => RenderMode::HalfBlock,
+            RenderMode::HalfBlock => RenderMode::Quadrant,
+            RenderMode::Quadrant => RenderMode::Sextant,
+            RenderMode::Sextant => RenderMode::Ascii,
+            RenderMode::Ascii if pixels => RenderMode::Pixels,
+            RenderMode::Ascii | RenderMode::Pixels => RenderMode::Braille,
+        }
+    }
+}
+
+/// How a braille cell picks its single foreground color from its lit dots.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CellColor {
+    Majority,
+    Average,
+}
+
+impl CellColor {
+    pub fn toggle(self) -> Self {
+        match self {
+            CellColor::Majority => CellColor::Average,
+            CellColor::Average => CellColor::Majority,
+        }
+    }
+}
+
+/// Braille dot bits in row-major order of the 2x4 cell.
+const BRAILLE_BITS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+const ASCII_RAMP: &[u8] = b".:-=+*#%@";
+
+/// Sextant glyph for a 2x3 mask (bit 0 top-left, bit 5 bottom-right). The
+/// Unicode block skips the patterns that already exist as half blocks.
+fn sextant(mask: u8) -> char {
+    match mask {
+        0 => ' ',
+        21 => '▌',
+        42 => '▐',
+        63 => '█',
+        m => {
+            let skipped = (m > 21) as u32 + (m > 42) as u32;
+            char::from_u32(0x1FB00 + m as u32 - 1 - skipped).unwrap_or(' ')
+        }
+    }
+}
+
+fn escape_iterations(pane: &FractalPane, x: f64, y: f64) -> Escape {
+    match pane.fractal_type {
+        FractalType::Mandelbrot => calculate_mandelbrot(x, y, pane.max_iters),
+        FractalType::BurningShip => calculate_burning_ship(x, y, pane.max_iters),
+        FractalType::Julia => calculate_julia(x, y, pane.julia_cx, pane.julia_cy, pane.max_iters),
+    }
+}
+
+/// Palette entry for a sample, if it is drawn at all. Interior points are only
+/// colored when the pane asks for it, by the cycle length found while iterating.
+fn palette_index(pane: &FractalPane, escape: Escape, palette_len: usize) -> Option<usize> {
+    match escape {
+        Escape::Escaped(iter) if iter < pane.max_iters => {
+            Some(pane.color_map.index(iter, pane.max_iters, palette_len))
+        }
+        Escape::Interior { period } if pane.interior_period && period > 0 => {
+            Some((period as usize + pane.color_map.offset) % palette_len)
+        }
+        _ => None,
+    }
+}
+
+/// Whether two samples are drawn the same; cycle lengths only matter when the interior is
+/// colored by them.
+fn drawn_alike(pane: &FractalPane, a: Escape, b: Escape) -> bool {
+    match (a, b) {
+        (Escape::Interior { .. }, Escape::Interior { .. }) => !pane.interior_period || a == b,
+        _ => a == b,
+    }
+}
+
+/// How a 1x sample grid is filled.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Strategy {
+    BruteForce,
+    MarianiSilver,
+}
+
+impl Strategy {
+    pub fn toggle(self) -> Self {
+        match self {
+            Strategy::BruteForce => Strategy::MarianiSilver,
+            Strategy::MarianiSilver => Strategy::BruteForce,
+        }
+    }
+}
+
+/// Edge length, in dots, of the tiles Mariani–Silver starts from; tiles are spread over rayon.
+const MS_TILE: u32 = 32;
+
+/// Rectangles this narrow are no longer subdivided but computed dot by dot.
+const MS_MIN_SIZE: u32 = 4;
+
+/// Escape results for the centers of a `cols x rows` dot grid, row-major from the top.
+pub fn sample_grid(view: Viewport, pane: &FractalPane, cols: u32, rows: u32) -> Vec<Escape> {
+    match pane.strategy {
+        Strategy::BruteForce => brute_force_grid(view, pane, cols, rows),
+        Strategy::MarianiSilver => mariani_silver_grid(view, pane, cols, rows),
+    }
+}
+
+fn brute_force_grid(view: Viewport, pane: &FractalPane, cols: u32, rows: u32) -> Vec<Escape> {
+    let width = view.right - view.left;
+    let height = view.top - view.bottom;
+
+    // The vector kernels skip cycle detection, so interior coloring and auto iterations,
+    // which both need to tell cycles from undecided points, stay on the scalar path.
+    #[cfg(feature = "simd")]
+    if !pane.interior_period && !pane.auto_iters {
+        let kernel = match pane.fractal_type {
+            FractalType::Mandelbrot => Kernel::Mandelbrot,
+            FractalType::BurningShip => Kernel::BurningShip,
+            FractalType::Julia => Kernel::Julia { cx: pane.julia_cx, cy: pane.julia_cy },
+        };
+        return (0..rows).into_par_iter()
+            .flat_map_iter(|j| {
+                let y = view.top - ((j as f64 + 0.5) / rows as f64) * height;
+                let xs: Vec<f64> = (0..cols)
+                    .map(|i| view.left + ((i as f64 + 0.5) / cols as f64) * width)
+                    .collect();
+                escape_row(kernel, &xs, y, pane.max_iters)
+            })
+            .collect();
+    }
+
+    (0..rows).into_par_iter()
+        .flat_map_iter(|j| {
+            let y = view.top - ((j as f64 + 0.5) / rows as f64) * height;
+            (0..cols).map(move |i| {
+                let x = view.left + ((i as f64 + 0.5) / cols as f64) * width;
+                escape_iterations(pane, x, y)
+            })
+        })
+        .collect()
+}
+
+/// Mariani–Silver subdivision: a rectangle whose border samples are all drawn
+/// alike is flood-filled with the first of them, otherwise it is split into
+/// quarters. Only borders and non-uniform detail are ever iterated.
+fn mariani_silver_grid(view: Viewport, pane: &FractalPane, cols: u32, rows: u32) -> Vec<Escape> {
+    let width = view.right - view.left;
+    let height = view.top - view.bottom;
+    let sample = |i: u32, j: u32| {
+        let x = view.left + ((i as f64 + 0.5) / cols as f64) * width;
+        let y = view.top - ((j as f64 + 0.5) / rows as f64) * height;
+        escape_iterations(pane, x, y)
+    };
+
+    let tiles: Vec<(u32, u32)> = (0..rows).step_by(MS_TILE as usize)
+        .flat_map(|ty| (0..cols).step_by(MS_TILE as usize).map(move |tx| (tx, ty)))
+        .collect();
+
+    let filled: Vec<(u32, u32, u32, Vec<Option<Escape>>)> = tiles.into_par_iter()
+        .map(|(tx, ty)| {
+            let w = MS_TILE.min(cols - tx);
+            let h = MS_TILE.min(rows - ty);
+            let mut tile = vec![None; (w * h) as usize];
+            let tile_sample = |i: u32, j: u32| sample(tx + i, ty + j);
+            subdivide(&mut tile, w, (0, 0, w, h), &tile_sample, &|a, b| drawn_alike(pane, a, b));
+            (tx, ty, w, tile)
+        })
+        .collect();
+
+    let mut grid = vec![Escape::Escaped(0); (cols * rows) as usize];
+    for (tx, ty, w, tile) in filled {
+        for (k, escape) in tile.into_iter().enumerate() {
+            let (i, j) = (tx + k as u32 % w, ty + k as u32 / w);
+            grid[(j * cols + i) as usize] = escape.unwrap_or(Escape::Escaped(0));
+        }
+    }
+    grid
+}
+
+/// Fills the rectangle `(x, y, w, h)` of a tile that is `stride` dots wide.
+fn subdivide(
+    tile: &mut [Option<Escape>],
+    stride: u32,
+    rect: (u32, u32, u32, u32),
+    sample: &impl Fn(u32, u32) -> Escape,
+    alike: &impl Fn(Escape, Escape) -> bool,
 ) {
-    let width = right - left;
-    let height = top - bottom;
-
-    // Dynamic resolution based on 2x4 braille grid
-    let density_x = (area.width as u32 * 2).max(10);
-    let density_y = (area.height as u32 * 4).max(10);
-
-    let colors = get_palette_colors(palette);
-    let palette_len = colors.len();
-
-    // Parallelize calculation using Rayon's map-reduce
-    let batches = (0..density_x).into_par_iter()
-        .fold(
-            || vec![Vec::new(); palette_len],
-            |mut local_batches, i| {
-                for j in 0..density_y {
-                    let x = left + (i as f64 / density_x as f64) * width;
-                    let y = bottom + (j as f64 / density_y as f64) * height;
-
-                    let iterations = match fractal_type {
-                        FractalType::Mandelbrot => calculate_mandelbrot(x, y, max_iters),
-                        FractalType::BurningShip => calculate_burning_ship(x, y, max_iters),
-                        FractalType::Julia => calculate_julia(x, y, julia_cx, julia_cy, max_iters),
+    let (x, y, w, h) = rect;
+    let at = |tile: &mut [Option<Escape>], i: u32, j: u32| -> Escape {
+        let slot = &mut tile[(j * stride + i) as usize];
+        *slot.get_or_insert_with(|| sample(i, j))
+    };
+
+    let border: Vec<(u32, u32)> = (x..x + w).flat_map(|i| [(i, y), (i, y + h - 1)])
+        .chain((y..y + h).flat_map(|j| [(x, j), (x + w - 1, j)]))
+        .collect();
+    let first = at(tile, x, y);
+    let uniform = border.into_iter().fold(true, |uniform, (i, j)| alike(at(tile, i, j), first) && uniform);
+
+    if uniform {
+        for j in y + 1..y + h - 1 {
+            for i in x + 1..x + w - 1 {
+                tile[(j * stride + i) as usize] = Some(first);
+            }
+        }
+    } else if w <= MS_MIN_SIZE || h <= MS_MIN_SIZE {
+        for j in y..y + h {
+            for i in x..x + w {
+                at(tile, i, j);
+            }
+        }
+    } else {
+        // Quarters share their middle row and column, which are already computed once.
+        let (hw, hh) = (w / 2, h / 2);
+        for (qx, qw) in [(x, hw + 1), (x + hw, w - hw)] {
+            for (qy, qh) in [(y, hh + 1), (y + hh, h - hh)] {
+                subdivide(tile, stride, (qx, qy, qw, qh), sample, alike);
+            }
+        }
+    }
+}
+
+/// Share of a dot's samples that must be colored for the dot to be drawn.
+const COVERAGE_THRESHOLD: f64 = 0.5;
+
+/// Deterministic sub-dot offset in [0, 1)², stable between frames so jitter does not flicker.
+fn jitter(i: u32, j: u32, sample: u32) -> (f64, f64) {
+    let mut h = i.wrapping_mul(0x9E37_79B1) ^ j.wrapping_mul(0x85EB_CA77) ^ sample.wrapping_mul(0xC2B2_AE3D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2C1B_3C6D);
+    h ^= h >> 12;
+    ((h & 0xFFFF) as f64 / 65536.0, (h >> 16) as f64 / 65536.0)
+}
+
+/// Supersampled grid: each dot takes `pane.samples`² samples and reports the
+/// mean iterations plus the average palette color, or `None` when too few
+/// samples were colored to pass the coverage threshold, and how many of its
+/// samples hit the iteration limit.
+pub fn supersample_grid(view: Viewport, pane: &FractalPane, colors: &[Color], cols: u32, rows: u32) -> Vec<(u32, Option<[u8; 3]>, u32)> {
+    let width = view.right - view.left;
+    let height = view.top - view.bottom;
+    let n = pane.samples.max(1);
+    let rgb: Vec<[u8; 3]> = colors.iter().map(|&c| to_rgb(c)).collect();
+
+    (0..rows).into_par_iter()
+        .flat_map_iter(|j| {
+            let rgb = &rgb;
+            (0..cols).map(move |i| {
+                let mut colored = 0u32;
+                let mut limit_hits = 0u32;
+                let mut iter_sum = 0u64;
+                let mut color_sum = [0u32; 3];
+
+                for s in 0..n * n {
+                    let (ox, oy) = if pane.jitter {
+                        let (rx, ry) = jitter(i, j, s);
+                        (((s % n) as f64 + rx) / n as f64, ((s / n) as f64 + ry) / n as f64)
+                    } else {
+                        (((s % n) as f64 + 0.5) / n as f64, ((s / n) as f64 + 0.5) / n as f64)
                     };
+                    let x = view.left + ((i as f64 + ox) / cols as f64) * width;
+                    let y = view.top - ((j as f64 + oy) / rows as f64) * height;
 
-                    if iterations < max_iters {
-                        let color_index = (iterations as usize) % palette_len;
-                        local_batches[color_index].push((x, y));
+                    let escape = escape_iterations(pane, x, y);
+                    limit_hits += escape.hit_limit() as u32;
+                    if let Some(index) = palette_index(pane, escape, rgb.len()) {
+                        colored += 1;
+                        iter_sum += escape.iterations(pane.max_iters) as u64;
+                        for (acc, v) in color_sum.iter_mut().zip(rgb[index]) {
+                            *acc += v as u32;
+                        }
                     }
                 }
-                local_batches
+
+                if (colored as f64) < COVERAGE_THRESHOLD * (n * n) as f64 {
+                    (pane.max_iters, None, limit_hits)
+                } else {
+                    ((iter_sum / colored as u64) as u32, Some(color_sum.map(|v| (v / colored) as u8)), limit_hits)
+                }
+            })
+        })
+        .collect()
+}
+
+/// Renders a pane to packed RGB8 pixels, interior points in black, along with the share of
+/// samples that hit the iteration limit, for auto iterations.
+pub fn render_rgb(view: Viewport, pane: &FractalPane, width: u32, height: u32) -> (Vec<u8>, f64) {
+    let colors = get_palette_rgb(pane.palette);
+
+    if pane.samples > 1 {
+        let dots = supersample_grid(view, pane, &colors, width, height);
+        let hits: u64 = dots.iter().map(|&(_, _, hits)| hits as u64).sum();
+        let ratio = share(hits, dots.len() as u64 * (pane.samples as u64).pow(2));
+        let rgb = dots.into_iter().flat_map(|(_, rgb, _)| rgb.unwrap_or([0, 0, 0])).collect();
+        return (rgb, ratio);
+    }
+
+    let samples = sample_grid(view, pane, width, height);
+    let hits = samples.iter().filter(|escape| escape.hit_limit()).count();
+    let rgb = samples.par_iter()
+        .flat_map_iter(|&escape| {
+            palette_index(pane, escape, colors.len())
+                .map(|index| to_rgb(colors[index]))
+                .unwrap_or([0, 0, 0])
+        })
+        .collect();
+    (rgb, share(hits as u64, samples.len() as u64))
+}
+
+fn share(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// Renders a pane straight into the buffer, one terminal cell at a time, so
+/// each cell's colors come from its own samples rather than draw order.
+pub struct FractalWidget<'a> {
+    pub view: Viewport,
+    pub pane: &'a FractalPane,
+    pub caps: ColorCaps,
+    pub block: Block<'a>,
+}
+
+impl StatefulWidget for FractalWidget<'_> {
+    /// The share of samples that hit `max_iters` without settling into a cycle, for auto
+    /// iterations; left alone when there is nothing to draw.
+    type State = f64;
+
+    fn render(self, area: Rect, buf: &mut Buffer, limit_ratio: &mut f64) {
+        let inner = self.block.inner(area);
+        self.block.render(area, buf);
+        if inner.is_empty() {
+            return;
+        }
+
+        let pane = self.pane;
+        // Pixel panes only reach this widget when no graphics protocol is available.
+        let mode = match pane.render_mode {
+            RenderMode::Pixels => RenderMode::Braille,
+            mode => mode,
+        };
+        let (dx, dy) = mode.dots_per_cell();
+        let cols = inner.width as u32 * dx;
+        let rows = inner.height as u32 * dy;
+
+        let caps = self.caps;
+        let mut limit_hits = 0u64;
+        let sample_count = (cols * rows) as u64 * (pane.samples.max(1) as u64).pow(2);
+        let (samples, colored): (Vec<u32>, Vec<Option<Color>>) = if pane.samples > 1 {
+            let blend = get_blend_palette(pane.palette, caps.support);
+            supersample_grid(self.view, pane, &blend, cols, rows)
+                .into_iter()
+                .enumerate()
+                .map(|(k, (iterations, rgb, hits))| {
+                    limit_hits += hits as u64;
+                    let cell = (k as u32 % cols, k as u32 / cols);
+                    let color = rgb.map(|[r, g, b]| quantize(Color::Rgb(r, g, b), caps.support, caps.dither.then_some(cell)));
+                    (iterations, color)
+                })
+                .unzip()
+        } else {
+            // 1x fast path: one sample per dot, colored straight from the resolved palette.
+            let escapes = sample_grid(self.view, pane, cols, rows);
+            let colors = ResolvedPalette::new(pane.palette, caps);
+            let palette_len = colors.entries();
+            escapes.iter().enumerate()
+                .map(|(k, &escape)| {
+                    limit_hits += escape.hit_limit() as u64;
+                    let color = palette_index(pane, escape, palette_len)
+                        .map(|index| colors.color(colors.slot(index, k as u32 % cols, k as u32 / cols)));
+                    (escape.iterations(pane.max_iters), color)
+                })
+                .unzip()
+        };
+
+        if sample_count > 0 {
+            *limit_ratio = share(limit_hits, sample_count);
+        }
+
+        let dot_color = |i: u32, j: u32| colored[(j * cols + i) as usize];
+
+        for row in 0..inner.height as u32 {
+            for col in 0..inner.width as u32 {
+                let cell = &mut buf[(inner.x + col as u16, inner.y + row as u16)];
+                cell.reset();
+
+                if mode == RenderMode::Ascii {
+                    let iterations = samples[(row * cols + col) as usize];
+                    if let Some(color) = dot_color(col, row) {
+                        let t = (1.0 + iterations as f64).ln() / (1.0 + pane.max_iters as f64).ln();
+                        let ramp = ((t * ASCII_RAMP.len() as f64) as usize).min(ASCII_RAMP.len() - 1);
+                        cell.set_char(ASCII_RAMP[ramp] as char).set_fg(color);
+                    }
+                    continue;
+                }
+
+                let dots: Vec<Option<Color>> = (0..dy)
+                    .flat_map(|y| (0..dx).map(move |x| (x, y)))
+                    .map(|(x, y)| dot_color(col * dx + x, row * dy + y))
+                    .collect();
+
+                if mode == RenderMode::Braille {
+                    let (symbol, fg) = braille_cell(&dots, pane.cell_color, self.caps, (col, row));
+                    cell.set_char(symbol);
+                    if let Some(fg) = fg {
+                        cell.set_fg(fg);
+                    }
+                    continue;
+                }
+
+                let (fg, bg, mask) = split_colors(&dots);
+                let symbol = match mode {
+                    RenderMode::HalfBlock => match mask {
+                        0b00 => ' ',
+                        0b01 => '▀',
+                        0b10 => '▄',
+                        _ => '█',
+                    },
+                    RenderMode::Quadrant => QUADRANTS[mask as usize],
+                    _ => sextant(mask),
+                };
+
+                cell.set_char(symbol);
+                if let Some(fg) = fg {
+                    cell.set_fg(fg);
+                }
+                if let Some(bg) = bg {
+                    cell.set_bg(bg);
+                }
+            }
+        }
+    }
+}
+
+fn braille_cell(dots: &[Option<Color>], mode: CellColor, caps: ColorCaps, cell: (u32, u32)) -> (char, Option<Color>) {
+    let lit: Vec<Color> = dots.iter().flatten().copied().collect();
+    let mask = dots.iter().zip(BRAILLE_BITS)
+        .filter(|(dot, _)| dot.is_some())
+        .fold(0u8, |mask, (_, bit)| mask | bit);
+
+    let symbol = char::from_u32(0x2800 + mask as u32).unwrap_or(' ');
+    if lit.is_empty() {
+        return (symbol, None);
+    }
+
+    let color = match mode {
+        CellColor::Majority => {
+            let mut counts: Vec<(Color, usize)> = Vec::new();
+            for color in &lit {
+                match counts.iter_mut().find(|(c, _)| c == color) {
+                    Some((_, n)) => *n += 1,
+                    None => counts.push((*color, 1)),
+                }
             }
-        )
-        .reduce(
-            || vec![Vec::new(); palette_len],
-            |mut global_batches, thread_batches| {
-                for (i, points) in thread_batches.into_iter().enumerate() {
-                    global_batches[i].extend(points);
+            // max_by_key keeps the last of equal counts; reverse so ties go to the first seen color.
+            counts.iter().rev().max_by_key(|(_, n)| *n).map(|(c, _)| *c).unwrap_or(lit[0])
+        }
+        CellColor::Average => {
+            let mut sum = [0u32; 3];
+            for color in &lit {
+                for (acc, v) in sum.iter_mut().zip(to_rgb(*color)) {
+                    *acc += v as u32;
                 }
-                global_batches
             }
-        );
+            let [r, g, b] = sum.map(|v| (v / lit.len() as u32) as u8);
+            quantize(Color::Rgb(r, g, b), caps.support, caps.dither.then_some(cell))
+        }
+    };
+
+    (symbol, Some(color))
+}
 
-    // Sequential draw to Context
-    for (i, points) in batches.iter().enumerate() {
-        if !points.is_empty() {
-            ctx.draw(&ratatui::widgets::canvas::Points {
-                coords: points,
-                color: colors[i],
-            });
+/// Picks the two most common colors of a cell and assigns each dot to the
+/// nearer one; dots in the returned mask take the foreground color.
+fn split_colors(dots: &[Option<Color>]) -> (Option<Color>, Option<Color>, u8) {
+    let mut counts: Vec<(Option<Color>, usize)> = Vec::new();
+    for dot in dots {
+        match counts.iter_mut().find(|(c, _)| c == dot) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((*dot, 1)),
         }
     }
-}
\ No newline at end of file
+    counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+
+    let mut fg = counts[0].0;
+    let mut bg = counts.get(1).map(|c| c.0).unwrap_or(None);
+    // Empty dots are always drawn as the terminal background.
+    if fg.is_none() {
+        std::mem::swap(&mut fg, &mut bg);
+    }
+    let Some(fg_color) = fg else {
+        return (None, None, 0);
+    };
+
+    let fg_rgb = to_rgb(fg_color);
+    let bg_rgb = bg.map(to_rgb).unwrap_or([0, 0, 0]);
+    let mask = dots.iter().enumerate().fold(0u8, |mask, (bit, dot)| {
+        let on = match dot {
+            Some(c) if *c == fg_color => true,
+            Some(c) => distance(to_rgb(*c), fg_rgb) < distance(to_rgb(*c), bg_rgb),
+            None => false,
+        };
+        if on { mask | (1 << bit) } else { mask }
+    });
+
+    (fg, bg, mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Renders `pane` at `cols x rows` with both strategies and checks every dot is drawn alike.
+    fn assert_strategies_agree(mut pane: FractalPane, cols: u32, rows: u32) {
+        let view = Viewport::square(pane.center_x, pane.center_y, pane.scale, cols, rows);
+        pane.strategy = Strategy::BruteForce;
+        let brute = sample_grid(view, &pane, cols, rows);
+        pane.strategy = Strategy::MarianiSilver;
+        let subdivided = sample_grid(view, &pane, cols, rows);
+        for (k, (&a, &b)) in brute.iter().zip(&subdivided).enumerate() {
+            assert!(drawn_alike(&pane, a, b), "dot ({}, {}): {:?} vs {:?}", k as u32 % cols, k as u32 / cols, a, b);
+        }
+    }
+
+    fn pane(fractal_type: FractalType, center_x: f64, center_y: f64, scale: f64) -> FractalPane {
+        FractalPane { fractal_type, center_x, center_y, scale, max_iters: 64, ..FractalPane::default() }
+    }
+
+    #[test]
+    fn mariani_silver_matches_brute_force_on_main_cardioid() {
+        assert_strategies_agree(pane(FractalType::Mandelbrot, -0.25, 0.0, 1.0), 160, 96);
+    }
+
+    #[test]
+    fn mariani_silver_matches_brute_force_on_exterior_bands() {
+        assert_strategies_agree(pane(FractalType::Mandelbrot, -0.75, 0.0, 3.0), 200, 120);
+    }
+
+    #[test]
+    fn mariani_silver_matches_brute_force_on_julia_set() {
+        let julia = FractalPane { julia_cx: -0.8, julia_cy: 0.156, ..pane(FractalType::Julia, 0.0, 0.0, 2.5) };
+        assert_strategies_agree(julia, 160, 96);
+    }
+
+    #[test]
+    fn mariani_silver_fills_interior_tiles_whatever_their_cycle_length() {
+        // Inside the period-3 bulb some orbits settle into period 6 within the limit.
+        let pane = FractalPane { max_iters: 256, ..pane(FractalType::Mandelbrot, -0.122, 0.745, 0.05) };
+        let view = Viewport::square(pane.center_x, pane.center_y, pane.scale, 64, 64);
+        let calls = Cell::new(0);
+        let sample = |i: u32, j: u32| {
+            calls.set(calls.get() + 1);
+            let x = view.left + (i as f64 + 0.5) / 64.0 * (view.right - view.left);
+            let y = view.top - (j as f64 + 0.5) / 64.0 * (view.top - view.bottom);
+            escape_iterations(&pane, x, y)
+        };
+        let mut tile = vec![None; 64 * 64];
+        subdivide(&mut tile, 64, (0, 0, 64, 64), &sample, &|a, b| drawn_alike(&pane, a, b));
+        assert_eq!(calls.get(), 4 * 64 - 4, "only the tile border should be iterated");
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hooks::PaneNode;
+use crate::location::Location;
+
+/// Everything needed to rebuild the layout: the split tree with every pane's
+/// settings, and which pane had focus (by position, since ids are reassigned).
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub active: usize,
+    pub root: PaneNode,
+}
+
+/// `session.json` in the user data directory, or the working directory when there is none.
+pub fn default_path() -> PathBuf {
+    match dirs::data_dir() {
+        Some(dir) => dir.join("frac-tui-rs").join("session.json"),
+        None => PathBuf::from("frac-tui-session.json"),
+    }
+}
+
+pub fn save(session: &Session, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(session).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+pub fn load(path: &Path) -> io::Result<Session> {
+    let json = fs::read_to_string(path)?;
+    let session: Session = serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    check(&session.root, &mut 1).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(session)
+}
+
+/// Holds restored panes to the limits of the command line and config file, so a hand-edited
+/// session cannot start a pane that renders nothing, and splits to the shape the layout code
+/// relies on. `number` counts panes in layout order.
+fn check(node: &PaneNode, number: &mut usize) -> Result<(), String> {
+    match node {
+        PaneNode::Pane(pane) => {
+            let result = if (1..=8).contains(&pane.samples) {
+                Location::of(pane).validate()
+            } else {
+                Err(format!("samples must be between 1 and 8, got {}", pane.samples))
+            };
+            result.map_err(|e| format!("pane {}: {}", number, e))?;
+            *number += 1;
+            Ok(())
+        }
+        PaneNode::Split { children, weights, .. } => {
+            if children.len() < 2 {
+                return Err(format!(
+                    "before pane {}: a split needs at least two children, found {}",
+                    number,
+                    children.len()
+                ));
+            }
+            // Missing weights split evenly.
+            if !weights.is_empty() && weights.len() != children.len() {
+                return Err(format!(
+                    "before pane {}: a split of {} children has {} weights",
+                    number,
+                    children.len(),
+                    weights.len()
+                ));
+            }
+            children.iter().try_for_each(|child| check(child, number))
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Vectorized escape-time kernels, enabled with the `simd` cargo feature.
+//!
+//! Points of a row are iterated in lockstep, four per AVX register or two per
+//! SSE2 register, with a per-lane mask freezing the count of lanes that have
+//! already escaped. The arithmetic mirrors `utils.rs` operation for operation,
+//! so iteration counts match the scalar kernels exactly. Interior points come
+//! back without a period since the vector loop does no cycle detection.
+
+use crate::utils::{mandelbrot_known_period, Escape};
+#[cfg(not(target_arch = "x86_64"))]
+use crate::utils::{calculate_burning_ship, calculate_julia, calculate_mandelbrot};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kernel {
+    Mandelbrot,
+    BurningShip,
+    Julia { cx: f64, cy: f64 },
+}
+
+/// Escape results for the points `(xs[i], y)`, vectorized when the CPU allows it.
+#[cfg(target_arch = "x86_64")]
+pub fn escape_row(kernel: Kernel, xs: &[f64], y: f64, max_iters: u32) -> Vec<Escape> {
+    escape_row_x86(kernel, xs, y, max_iters, is_x86_feature_detected!("avx"))
+}
+
+/// Escape results for the points `(xs[i], y)`, vectorized when the CPU allows it.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn escape_row(kernel: Kernel, xs: &[f64], y: f64, max_iters: u32) -> Vec<Escape> {
+    let (mut out, pending) = known_points(kernel, xs, y);
+    for i in pending {
+        out[i] = match kernel {
+            Kernel::Mandelbrot => calculate_mandelbrot(xs[i], y, max_iters),
+            Kernel::BurningShip => calculate_burning_ship(xs[i], y, max_iters),
+            Kernel::Julia { cx, cy } => calculate_julia(xs[i], y, cx, cy, max_iters),
+        };
+    }
+    out
+}
+
+/// Answers Mandelbrot points with a closed form, which never enter the vector loop, and
+/// returns the indices still to iterate.
+fn known_points(kernel: Kernel, xs: &[f64], y: f64) -> (Vec<Escape>, Vec<usize>) {
+    let mut out = vec![Escape::Escaped(0); xs.len()];
+    let pending = match kernel {
+        Kernel::Mandelbrot => (0..xs.len())
+            .filter(|&i| match mandelbrot_known_period(xs[i], y) {
+                Some(period) => {
+                    out[i] = Escape::Interior { period };
+                    false
+                }
+                None => true,
+            })
+            .collect(),
+        _ => (0..xs.len()).collect(),
+    };
+    (out, pending)
+}
+
+/// `escape_row` on AVX when `avx` is set, otherwise on SSE2; `avx` must only be set after
+/// runtime detection.
+#[cfg(target_arch = "x86_64")]
+fn escape_row_x86(kernel: Kernel, xs: &[f64], y: f64, max_iters: u32, avx: bool) -> Vec<Escape> {
+    let (mut out, pending) = known_points(kernel, xs, y);
+    let lanes = if avx { 4 } else { 2 };
+    for batch in pending.chunks(lanes) {
+        // Unused lanes start outside the escape radius, so a short batch still stops as soon
+        // as its real points have escaped.
+        let mut zx = [4.0; 4];
+        let mut zy = [0.0; 4];
+        let mut cx = [0.0; 4];
+        let mut cy = [0.0; 4];
+        for (lane, &i) in batch.iter().enumerate() {
+            (zx[lane], zy[lane], cx[lane], cy[lane]) = match kernel {
+                Kernel::Julia { cx, cy } => (xs[i], y, cx, cy),
+                _ => (0.0, 0.0, xs[i], y),
+            };
+        }
+
+        let burning = kernel == Kernel::BurningShip;
+        // SAFETY: the AVX path is only taken after runtime detection; SSE2 is part of the x86_64 baseline.
+        let results = unsafe {
+            if avx {
+                x86::escape_avx(zx, zy, cx, cy, max_iters, burning)
+            } else {
+                let lo = x86::escape_sse2([zx[0], zx[1]], [zy[0], zy[1]], [cx[0], cx[1]], [cy[0], cy[1]], max_iters, burning);
+                [lo[0], lo[1], Escape::Escaped(0), Escape::Escaped(0)]
+            }
+        };
+        for (lane, &i) in batch.iter().enumerate() {
+            out[i] = results[lane];
+        }
+    }
+    out
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use crate::utils::Escape;
+
+    fn to_escape(count: f64, still_bounded: bool) -> Escape {
+        if still_bounded {
+            Escape::Interior { period: 0 }
+        } else {
+            Escape::Escaped(count as u32)
+        }
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn escape_avx(zx: [f64; 4], zy: [f64; 4], cx: [f64; 4], cy: [f64; 4], max_iters: u32, burning: bool) -> [Escape; 4] {
+        let mut x = _mm256_loadu_pd(zx.as_ptr());
+        let mut y = _mm256_loadu_pd(zy.as_ptr());
+        let cx = _mm256_loadu_pd(cx.as_ptr());
+        let cy = _mm256_loadu_pd(cy.as_ptr());
+        let two = _mm256_set1_pd(2.0);
+        let four = _mm256_set1_pd(4.0);
+        let one = _mm256_set1_pd(1.0);
+        let sign = _mm256_set1_pd(-0.0);
+
+        let mut counts = _mm256_setzero_pd();
+        let mut active = _mm256_castsi256_pd(_mm256_set1_epi64x(-1));
+
+        for _ in 0..max_iters {
+            let x2 = _mm256_mul_pd(x, x);
+            let y2 = _mm256_mul_pd(y, y);
+            active = _mm256_and_pd(active, _mm256_cmp_pd(_mm256_add_pd(x2, y2), four, _CMP_LE_OQ));
+            if _mm256_movemask_pd(active) == 0 {
+                break;
+            }
+            counts = _mm256_add_pd(counts, _mm256_and_pd(active, one));
+
+            let (ax, ay) = if burning {
+                (_mm256_andnot_pd(sign, x), _mm256_andnot_pd(sign, y))
+            } else {
+                (x, y)
+            };
+            let new_y = _mm256_add_pd(_mm256_mul_pd(_mm256_mul_pd(two, ax), ay), cy);
+            x = _mm256_add_pd(_mm256_sub_pd(x2, y2), cx);
+            y = new_y;
+        }
+        let bounded = _mm256_cmp_pd(_mm256_add_pd(_mm256_mul_pd(x, x), _mm256_mul_pd(y, y)), four, _CMP_LE_OQ);
+        let bounded = _mm256_movemask_pd(_mm256_and_pd(active, bounded));
+
+        let mut c = [0.0; 4];
+        _mm256_storeu_pd(c.as_mut_ptr(), counts);
+        [0, 1, 2, 3].map(|lane| to_escape(c[lane], bounded & (1 << lane) != 0))
+    }
+
+    pub unsafe fn escape_sse2(zx: [f64; 2], zy: [f64; 2], cx: [f64; 2], cy: [f64; 2], max_iters: u32, burning: bool) -> [Escape; 2] {
+        let mut x = _mm_loadu_pd(zx.as_ptr());
+        let mut y = _mm_loadu_pd(zy.as_ptr());
+        let cx = _mm_loadu_pd(cx.as_ptr());
+        let cy = _mm_loadu_pd(cy.as_ptr());
+        let two = _mm_set1_pd(2.0);
+        let four = _mm_set1_pd(4.0);
+        let one = _mm_set1_pd(1.0);
+        let sign = _mm_set1_pd(-0.0);
+
+        let mut counts = _mm_setzero_pd();
+        let mut active = _mm_castsi128_pd(_mm_set1_epi64x(-1));
+
+        for _ in 0..max_iters {
+            let x2 = _mm_mul_pd(x, x);
+            let y2 = _mm_mul_pd(y, y);
+            active = _mm_and_pd(active, _mm_cmple_pd(_mm_add_pd(x2, y2), four));
+            if _mm_movemask_pd(active) == 0 {
+                break;
+            }
+            counts = _mm_add_pd(counts, _mm_and_pd(active, one));
+
+            let (ax, ay) = if burning {
+                (_mm_andnot_pd(sign, x), _mm_andnot_pd(sign, y))
+            } else {
+                (x, y)
+            };
+            let new_y = _mm_add_pd(_mm_mul_pd(_mm_mul_pd(two, ax), ay), cy);
+            x = _mm_add_pd(_mm_sub_pd(x2, y2), cx);
+            y = new_y;
+        }
+        let bounded = _mm_cmple_pd(_mm_add_pd(_mm_mul_pd(x, x), _mm_mul_pd(y, y)), four);
+        let bounded = _mm_movemask_pd(_mm_and_pd(active, bounded));
+
+        let mut c = [0.0; 2];
+        _mm_storeu_pd(c.as_mut_ptr(), counts);
+        [0, 1].map(|lane| to_escape(c[lane], bounded & (1 << lane) != 0))
+    }
+}
+
+
+#[cfg(all(test, target_arch = "x86_64"))]
+mod tests {
+    use super::*;
+    use crate::utils::{calculate_burning_ship, calculate_julia, calculate_mandelbrot};
+
+    /// Scalar results carry cycle lengths the vector loop does not detect, so interior points
+    /// only need to agree on being interior.
+    fn same_result(vector: Escape, scalar: Escape) -> bool {
+        match (vector, scalar) {
+            (Escape::Interior { .. }, Escape::Interior { .. }) => true,
+            _ => vector == scalar,
+        }
+    }
+
+    fn check_rows(kernel: Kernel, scalar: impl Fn(f64, f64) -> Escape, avx: bool) {
+        for len in [1, 2, 3, 4, 5, 7, 9, 61] {
+            for row in 0..24 {
+                let y = -1.2 + row as f64 * 0.1;
+                let xs: Vec<f64> = (0..len).map(|i| -2.0 + 2.5 * (i as f64 + 0.5) / len as f64).collect();
+                let vector = escape_row_x86(kernel, &xs, y, 200, avx);
+                for (&x, &v) in xs.iter().zip(&vector) {
+                    let s = scalar(x, y);
+                    assert!(same_result(v, s), "{:?} at ({}, {}), row of {}: {:?} vs {:?}", kernel, x, y, len, v, s);
+                }
+            }
+        }
+    }
+
+    fn check_kernels(avx: bool) {
+        check_rows(Kernel::Mandelbrot, |x, y| calculate_mandelbrot(x, y, 200), avx);
+        check_rows(Kernel::BurningShip, |x, y| calculate_burning_ship(x, y, 200), avx);
+        let julia = Kernel::Julia { cx: -0.8, cy: 0.156 };
+        check_rows(julia, |x, y| calculate_julia(x, y, -0.8, 0.156, 200), avx);
+    }
+
+    #[test]
+    fn sse2_matches_scalar_kernels() {
+        check_kernels(false);
+    }
+
+    #[test]
+    fn avx_matches_scalar_kernels() {
+        if is_x86_feature_detected!("avx") {
+            check_kernels(true);
+        }
+    }
+
+    #[test]
+    fn padding_lanes_do_not_hold_back_a_batch() {
+        // A single point that escapes at once must not be iterated alongside a never-escaping lane.
+        assert_eq!(escape_row_x86(Kernel::Mandelbrot, &[3.0], 0.0, u32::MAX, false), [Escape::Escaped(1)]);
+        if is_x86_feature_detected!("avx") {
+            assert_eq!(escape_row_x86(Kernel::Mandelbrot, &[3.0], 0.0, u32::MAX, true), [Escape::Escaped(1)]);
+        }
+    }
+}
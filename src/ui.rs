@@ -1,11 +1,53 @@
 use ratatui::{
     prelude::*,
-    symbols::Marker,
-    widgets::canvas::Canvas,
-    widgets::{Block, Borders, Paragraph, Clear, Table, Row},
+    widgets::{Block, Borders, Paragraph, Clear, Table, TableState, Row},
 };
-use crate::hooks::{App, PaneNode, FractalType, InputField};
-use crate::render::draw_fractal;
+use crate::hooks::{App, BookmarkInput, PaneNode, FractalType, InputField};
+use crate::color::ColorCaps;
+use crate::config::{Action, Defaults};
+use crate::graphics::{cell_pixels, GraphicsProtocol, ImageKey, PendingImage};
+use crate::history::ViewState;
+use crate::location::Location;
+use crate::render::{render_rgb, FractalWidget, RenderMode, Strategy, Viewport};
+
+/// Longest side of an image sent to terminals that scale it into the pane themselves.
+const MAX_SCALED_PIXELS: u32 = 1024;
+
+/// Popup sizes in percent of the screen, as passed to `centered_rect`.
+const HELP_POPUP: (u16, u16) = (60, 80);
+const BOOKMARKS_POPUP: (u16, u16) = (80, 60);
+const HISTORY_POPUP: (u16, u16) = (60, 60);
+const BOOKMARK_INPUT_POPUP: (u16, u16) = (40, 20);
+const QUIT_POPUP: (u16, u16) = (60, 20);
+
+struct DrawContext<'a> {
+    active_id: usize,
+    pane_counter: usize,
+    caps: ColorCaps,
+    graphics: Option<GraphicsProtocol>,
+    images: &'a mut Vec<PendingImage>,
+    /// Where a pane whose view overflowed starts over.
+    defaults: Defaults,
+    /// Last frame's images, taken over by panes whose image has not changed.
+    previous: Vec<PendingImage>,
+    /// Open popups; inline images would be drawn over them, so panes underneath fall back to text.
+    popups: Vec<Rect>,
+}
+
+/// Screen areas of the popups that are open.
+fn popup_areas(app: &App, screen: Rect) -> Vec<Rect> {
+    [
+        (app.show_help_popup, HELP_POPUP),
+        (app.show_bookmarks, BOOKMARKS_POPUP),
+        (app.show_history, HISTORY_POPUP),
+        (app.bookmark_input.is_some(), BOOKMARK_INPUT_POPUP),
+        (app.show_quit_popup, QUIT_POPUP),
+    ]
+        .into_iter()
+        .filter(|&(open, _)| open)
+        .map(|(_, (percent_x, percent_y))| centered_rect(percent_x, percent_y, screen))
+        .collect()
+}
 
 pub fn ui(f: &mut Frame, app: &mut App) {
     let main_layout = Layout::default()
@@ -16,48 +58,93 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         ])
         .split(f.area());
 
-    let header_text = "Shift+{u,d,l,r}: Split Pane | Shift+x: Close Pane | Tab: Cycle | {1..9}: Switch | r: Reset | Click: Focus | h: Help | Q: Quit";
-
+    let keymap = &app.config.keymap;
+    let splits = [Action::SplitUp, Action::SplitDown, Action::SplitLeft, Action::SplitRight].map(|action| keymap.label(action));
+    let header_text = format!(
+        "{}: Split Pane | {}: Close Pane | {}: Cycle | {}..{}: Switch | {}: Reset | Click: Focus | {}: Help | {}: Quit",
+        splits.join(","), keymap.label(Action::ClosePane), keymap.label(Action::CycleFocus),
+        keymap.label(Action::FocusPane(1)), keymap.label(Action::FocusPane(9)),
+        keymap.label(Action::Reset), keymap.label(Action::Help), keymap.label(Action::Quit),
+    );
+
+    let mut header_block = Block::default().borders(Borders::ALL).title("Frac-tui");
+    if app.recording {
+        header_block = header_block.title(Line::from(" ● REC ").style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)).right_aligned());
+    }
     let header = Paragraph::new(header_text)
-        .block(Block::default().borders(Borders::ALL).title("Frac-tui"))
+        .block(header_block)
         .style(Style::default().fg(Color::Cyan));
 
     f.render_widget(header, main_layout[0]);
 
-    let mut pane_counter = 1;
-    draw_tree(f, &mut app.root, main_layout[1], app.active_pane_id, &mut pane_counter);
+    let previous = std::mem::take(&mut app.pending_images);
+    let popups = popup_areas(app, f.area());
+    let mut ctx = DrawContext {
+        active_id: app.active_pane_id,
+        pane_counter: 1,
+        caps: app.color_caps,
+        graphics: app.graphics,
+        images: &mut app.pending_images,
+        defaults: app.config.defaults,
+        previous,
+        popups,
+    };
+    draw_tree(f, &mut app.root, main_layout[1], &mut ctx);
 
     if app.show_help_popup {
-        let popup_area = centered_rect(60, 60, f.area());
-
-        let rows = vec![
-            Row::new(vec!["Key", "Action"]),
-            Row::new(vec!["Arrow Keys", "Pan view"]),
-            Row::new(vec!["Mouse Wheel", "Zoom in/out (cursor)"]),
-            Row::new(vec!["+/-", "Zoom in/out (center)"]),
-            Row::new(vec!["Space", "Cycle Palette"]),
-            Row::new(vec!["b", "Cycle Fractal Type"]),
-            Row::new(vec!["d/s", "Increase/Decrease Iterations"]),
-            Row::new(vec!["r", "Reset View"]),
-            Row::new(vec!["Shift + Arrow", "Split Pane (Direction)"]),
-            Row::new(vec!["Shift + x", "Close Active Pane"]),
-            Row::new(vec!["Tab", "Cycle Focus"]),
-            Row::new(vec!["1-9", "Switch Focus to Pane #"]),
-            Row::new(vec!["h", "Toggle Help"]),
-            Row::new(vec!["q / Esc", "Quit"]),
+        let popup_area = centered_rect(HELP_POPUP.0, HELP_POPUP.1, f.area());
+        let keymap = &app.config.keymap;
+
+        let mut rows = vec![
+            Row::new(vec!["Mouse Wheel".to_string(), "Zoom in/out (cursor)".to_string()]),
+            Row::new(vec!["Click".to_string(), "Focus Pane".to_string()]),
         ];
+        rows.extend(Action::all().map(|(action, description)| {
+            let keys = keymap.label(action);
+            let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys };
+            Row::new(vec![keys, description.to_string()])
+        }));
 
         let table = Table::new(rows, [Constraint::Percentage(30), Constraint::Percentage(70)])
-            .block(Block::default().title(" Help ").borders(Borders::ALL))
+            .block(Block::default().title(" Help ").title_bottom(" Up/Down: Scroll ").borders(Borders::ALL))
             .header(Row::new(vec!["Key", "Action"]).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)))
             .column_spacing(1);
 
+        let mut state = TableState::default().with_offset(app.help_scroll);
+        f.render_widget(Clear, popup_area);
+        f.render_stateful_widget(table, popup_area, &mut state);
+    }
+
+    if app.show_bookmarks {
+        draw_bookmarks(f, app);
+    }
+
+    if app.show_history {
+        draw_history(f, app);
+    }
+
+    if let Some(input) = app.bookmark_input {
+        let popup_area = centered_rect(BOOKMARK_INPUT_POPUP.0, BOOKMARK_INPUT_POPUP.1, f.area());
+        let title = match input {
+            BookmarkInput::New => " New Bookmark ",
+            BookmarkInput::Rename => " Rename Bookmark ",
+        };
+
+        let prompt = Paragraph::new(format!("Name: {}_", app.bookmark_name))
+            .block(
+                Block::default()
+                    .title(title)
+                    .title_bottom(" Enter: Save | Esc: Cancel ")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::Yellow))
+            );
+
         f.render_widget(Clear, popup_area);
-        f.render_widget(table, popup_area);
+        f.render_widget(prompt, popup_area);
     }
 
     if app.show_quit_popup {
-        let popup_area = centered_rect(60, 20, f.area());
+        let popup_area = centered_rect(QUIT_POPUP.0, QUIT_POPUP.1, f.area());
 
         let popup_block = Paragraph::new("Are you sure you want to quit?\n\n(y) Yes / (n) No")
             .block(
@@ -74,6 +161,121 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     }
 }
 
+/// Saved locations on the left, a live render of the selected one on the right.
+fn draw_bookmarks(f: &mut Frame, app: &App) {
+    let popup_area = centered_rect(BOOKMARKS_POPUP.0, BOOKMARKS_POPUP.1, f.area());
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(popup_area);
+
+    let rows: Vec<Row> = app.bookmarks.iter().map(|b| {
+        Row::new(vec![
+            b.name.clone(),
+            format!("{:?}", b.location.fractal_type),
+            format!("{:.4}, {:.4}", b.location.center_x, b.location.center_y),
+            format!("{:.1e}", b.location.scale),
+            b.location.max_iters.to_string(),
+        ])
+    }).collect();
+
+    let table = Table::new(rows, [
+        Constraint::Fill(1),
+        Constraint::Length(11),
+        Constraint::Length(16),
+        Constraint::Length(7),
+        Constraint::Length(6),
+    ])
+        .block(
+            Block::default()
+                .title(" Bookmarks ")
+                .title_bottom(" Enter: Jump | r: Rename | d: Delete | Esc: Close ")
+                .borders(Borders::ALL)
+        )
+        .header(Row::new(vec!["Name", "Type", "Center", "Scale", "Iters"]).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)))
+        .row_highlight_style(Style::default().bg(Color::DarkGray))
+        .column_spacing(1);
+
+    let mut state = TableState::default().with_selected((!app.bookmarks.is_empty()).then_some(app.bookmark_selected));
+    f.render_widget(Clear, popup_area);
+    f.render_stateful_widget(table, chunks[0], &mut state);
+
+    let block = Block::default().title(" Preview ").borders(Borders::ALL);
+    let (Some(bookmark), Some(active)) = (app.bookmarks.get(app.bookmark_selected), app.active_pane()) else {
+        f.render_widget(block, chunks[1]);
+        return;
+    };
+
+    // The active pane's render settings, at the bookmarked location.
+    let mut pane = active.clone();
+    bookmark.location.apply(&mut pane);
+    let area = chunks[1];
+    let aspect_ratio = area.width as f64 / (area.height as f64 * 2.0).max(1.0);
+    let width = pane.scale * aspect_ratio;
+    let viewport = Viewport {
+        left: pane.center_x - width / 2.0,
+        right: pane.center_x + width / 2.0,
+        bottom: pane.center_y - pane.scale / 2.0,
+        top: pane.center_y + pane.scale / 2.0,
+    };
+    f.render_stateful_widget(
+        FractalWidget { view: viewport, pane: &pane, caps: app.color_caps, block },
+        area,
+        &mut 0.0,
+    );
+}
+
+/// The active pane's undo steps, its current view and the steps that can be redone.
+fn draw_history(f: &mut Frame, app: &App) {
+    let popup_area = centered_rect(HISTORY_POPUP.0, HISTORY_POPUP.1, f.area());
+    let (states, current) = match app.active_pane() {
+        Some(pane) => pane.history.timeline(ViewState::of(pane)),
+        None => return,
+    };
+
+    let rows: Vec<Row> = states.iter().enumerate().map(|(i, state)| {
+        let step = match i.cmp(&current) {
+            std::cmp::Ordering::Less => format!("-{}", current - i),
+            std::cmp::Ordering::Equal => "now".to_string(),
+            std::cmp::Ordering::Greater => format!("+{}", i - current),
+        };
+        let iters = if state.auto_iters { "auto".to_string() } else { state.max_iters.to_string() };
+        let row = Row::new(vec![
+            step,
+            format!("{:?}", state.fractal_type),
+            format!("{:.4}, {:.4}", state.center_x, state.center_y),
+            format!("{:.1e}", state.scale),
+            iters,
+        ]);
+        if i == current {
+            row.style(Style::default().fg(Color::Green))
+        } else {
+            row
+        }
+    }).collect();
+
+    let table = Table::new(rows, [
+        Constraint::Length(4),
+        Constraint::Length(11),
+        Constraint::Fill(1),
+        Constraint::Length(7),
+        Constraint::Length(6),
+    ])
+        .block(
+            Block::default()
+                .title(" History ")
+                .title_bottom(" Enter: Jump | Esc: Close ")
+                .borders(Borders::ALL)
+        )
+        .header(Row::new(vec!["Step", "Type", "Center", "Scale", "Iters"]).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)))
+        .row_highlight_style(Style::default().bg(Color::DarkGray))
+        .column_spacing(1);
+
+    let mut state = TableState::default().with_selected(Some(app.history_selected));
+    f.render_widget(Clear, popup_area);
+    f.render_stateful_widget(table, popup_area, &mut state);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -94,12 +296,12 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn draw_tree(f: &mut Frame, node: &mut PaneNode, area: Rect, active_id: usize, pane_counter: &mut usize) {
+fn draw_tree(f: &mut Frame, node: &mut PaneNode, area: Rect, ctx: &mut DrawContext) {
     match node {
         PaneNode::Pane(pane) => {
             pane.area = area;
 
-            let is_active = pane.id == active_id;
+            let is_active = pane.id == ctx.active_id;
             let border_style = if is_active {
                 Style::default().fg(Color::Green)
             } else {
@@ -116,43 +318,117 @@ fn draw_tree(f: &mut Frame, node: &mut PaneNode, area: Rect, active_id: usize, p
             let mut y_top = pane.center_y + height / 2.0;
 
             if !x_left.is_finite() || !x_right.is_finite() || !y_bottom.is_finite() || !y_top.is_finite() {
-                pane.center_x = -0.75;
-                pane.center_y = 0.0;
-                pane.scale = 3.0;
-                let aspect = area.width as f64 / (area.height as f64 * 2.0).max(1.0);
-                let w = 3.0 * aspect;
-                x_left = -0.75 - w/2.0;
-                x_right = -0.75 + w/2.0;
-                y_bottom = -1.5;
-                y_top = 1.5;
+                pane.center_x = ctx.defaults.center_x;
+                pane.center_y = ctx.defaults.center_y;
+                pane.scale = ctx.defaults.scale;
+                let w = pane.scale * aspect_ratio;
+                x_left = pane.center_x - w / 2.0;
+                x_right = pane.center_x + w / 2.0;
+                y_bottom = pane.center_y - pane.scale / 2.0;
+                y_top = pane.center_y + pane.scale / 2.0;
+            }
+
+            if pane.auto_iters {
+                pane.max_iters = pane.auto_max_iters();
             }
 
-            let p_palette = pane.palette;
             let p_type = pane.fractal_type;
-            let p_iters = pane.max_iters;
-            let p_julia_cx = pane.julia_cx;
-            let p_julia_cy = pane.julia_cy;
-
-            let display_id = *pane_counter;
-            *pane_counter += 1;
-
-            let title = format!("{}: [{:?}, {}, {:?}]", display_id, p_type, p_iters, p_palette);
-
-            let canvas = Canvas::default()
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(title)
-                        .border_style(border_style)
-                )
-                .marker(Marker::Braille)
-                .x_bounds([x_left, x_right])
-                .y_bounds([y_bottom, y_top])
-                .paint(move |ctx| {
-                    draw_fractal(ctx, x_left, x_right, y_bottom, y_top, p_palette, p_type, p_iters, area, p_julia_cx, p_julia_cy);
-                });
-
-            f.render_widget(canvas, area);
+            let iters_label = if pane.auto_iters {
+                format!("auto {}", pane.max_iters)
+            } else {
+                pane.max_iters.to_string()
+            };
+
+            let display_id = ctx.pane_counter;
+            ctx.pane_counter += 1;
+
+            let title = format!(
+                "{}: [{:?}, {}, {:?} {}, {:?} {}x{}{}{}]",
+                display_id, p_type, iters_label, pane.palette, pane.color_map.label(), pane.render_mode,
+                pane.samples, pane.samples, if pane.jitter { "j" } else { "" },
+                if pane.strategy == Strategy::MarianiSilver { " ms" } else { "" }
+            );
+            let viewport = Viewport { left: x_left, right: x_right, bottom: y_bottom, top: y_top };
+
+            let mut block = Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(border_style);
+
+            let status = match pane.active_input {
+                Some(InputField::Export) => Some(format!("Export size: {}_", pane.input_buffer)),
+                Some(InputField::Zoom) => Some(format!("Zoom frames, size [gif]: {}_", pane.input_buffer)),
+                Some(InputField::Location) => Some(format!("Location: {}_", pane.input_buffer)),
+                Some(InputField::ImportFile) => Some(format!("Import .kfr/.xpf: {}_", pane.input_buffer)),
+                Some(InputField::ExportFile) => Some(format!("Export .kfr/.xpf: {}_", pane.input_buffer)),
+                _ => pane.export.as_ref().map(|job| job.status()).or_else(|| pane.notice.clone()),
+            };
+            if let Some(status) = status {
+                block = block.title_bottom(Line::from(status).style(Style::default().fg(Color::Yellow)));
+            }
+
+            let covered = ctx.popups.iter().any(|popup| popup.intersects(area));
+            match ctx.graphics {
+                Some(protocol) if pane.render_mode == RenderMode::Pixels && !covered => {
+                    let inner = block.inner(area);
+                    f.render_widget(block, area);
+
+                    // Julia keeps its two input rows as text below the image.
+                    let reserved = if p_type == FractalType::Julia { 2 } else { 0 };
+                    let image_area = Rect { height: inner.height.saturating_sub(reserved), ..inner };
+
+                    // The image is written after the frame; ratatui must leave these cells alone.
+                    let buf = f.buffer_mut();
+                    for y in image_area.top()..image_area.bottom() {
+                        for x in image_area.left()..image_area.right() {
+                            buf[(x, y)].set_skip(true);
+                        }
+                    }
+
+                    if !image_area.is_empty() {
+                        let (cell_w, cell_h) = cell_pixels();
+                        let mut width = image_area.width as u32 * cell_w;
+                        let mut height = image_area.height as u32 * cell_h;
+                        if protocol != GraphicsProtocol::Sixel && width.max(height) > MAX_SCALED_PIXELS {
+                            let shrink = MAX_SCALED_PIXELS as f64 / width.max(height) as f64;
+                            width = ((width as f64 * shrink) as u32).max(1);
+                            height = ((height as f64 * shrink) as u32).max(1);
+                        }
+
+                        let key = ImageKey {
+                            pane_id: pane.id,
+                            area: image_area,
+                            width,
+                            height,
+                            location: Location::of(pane),
+                            color_map: pane.color_map,
+                            samples: pane.samples,
+                            jitter: pane.jitter,
+                            interior_period: pane.interior_period,
+                        };
+                        let image = match ctx.previous.iter().position(|image| image.key == key) {
+                            Some(index) => ctx.previous.swap_remove(index),
+                            None => {
+                                // Pixels are square, so the image is rendered for its own aspect ratio.
+                                let image_view = Viewport::square(pane.center_x, pane.center_y, pane.scale, width, height);
+                                let (rgb, limit_ratio) = render_rgb(image_view, pane, width, height);
+                                pane.limit_ratio = limit_ratio;
+                                PendingImage { key, area: image_area, width, height, rgb }
+                            }
+                        };
+                        ctx.images.push(image);
+                    }
+                }
+                _ => {
+                    let mut limit_ratio = pane.limit_ratio;
+                    f.render_stateful_widget(
+                        FractalWidget { view: viewport, pane: &*pane, caps: ctx.caps, block },
+                        area,
+                        &mut limit_ratio,
+                    );
+                    pane.limit_ratio = limit_ratio;
+                }
+            }
 
             if p_type == FractalType::Julia {
                 let cx_area = Rect {
@@ -202,12 +478,12 @@ fn draw_tree(f: &mut Frame, node: &mut PaneNode, area: Rect, active_id: usize, p
                 f.render_widget(cy_widget, cy_area);
             }
         }
-        PaneNode::Split { direction, children } => {
+        PaneNode::Split { direction, children, weights } => {
             if children.is_empty() { return; }
 
-            let ratio = 100 / children.len() as u16;
-            let constraints: Vec<Constraint> = children.iter()
-                .map(|_| Constraint::Percentage(ratio))
+            // Fill shares out every cell, so no rounding remainder is left empty.
+            let constraints: Vec<Constraint> = (0..children.len())
+                .map(|i| Constraint::Fill(weights.get(i).copied().unwrap_or(1)))
                 .collect();
 
             let chunks = Layout::default()
@@ -217,7 +493,7 @@ fn draw_tree(f: &mut Frame, node: &mut PaneNode, area: Rect, active_id: usize, p
 
             for (i, child) in children.iter_mut().enumerate() {
                 if i < chunks.len() {
-                    draw_tree(f, child, chunks[i], active_id, pane_counter);
+                    draw_tree(f, child, chunks[i], ctx);
                 }
             }
         }
@@ -1,41 +1,85 @@
-pub fn calculate_mandelbrot(cx: f64, cy: f64, max_iters: u32) -> u32 {
-    let mut x = 0.0;
-    let mut y = 0.0;
-    let mut iter = 0;
+/// Two orbit points closer than this (per axis) are treated as the same point of a cycle.
+const PERIOD_EPSILON: f64 = 1e-13;
 
-    while (x * x + y * y <= 4.0) && (iter < max_iters) {
-        let x_temp = x * x - y * y + cx;
-        y = 2.0 * x * y + cy;
-        x = x_temp;
-        iter += 1;
-    }
-    iter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Escape {
+    /// The orbit left the radius-2 disc after this many iterations.
+    Escaped(u32),
+    /// The orbit stayed bounded. `period` is the detected cycle length, or 0
+    /// when the iteration limit was reached before a cycle was found.
+    Interior { period: u32 },
 }
 
-pub fn calculate_burning_ship(cx: f64, cy: f64, max_iters: u32) -> u32 {
-    let mut x = 0.0;
-    let mut y = 0.0;
-    let mut iter = 0;
+impl Escape {
+    pub fn iterations(self, max_iters: u32) -> u32 {
+        match self {
+            Escape::Escaped(iter) => iter,
+            Escape::Interior { .. } => max_iters,
+        }
+    }
 
-    while (x * x + y * y <= 4.0) && (iter < max_iters) {
-        let x_temp = x * x - y * y + cx;
-        y = 2.0 * x.abs() * y.abs() + cy;
-        x = x_temp;
-        iter += 1;
+    /// Still undecided at the iteration limit: bounded, but not settled into a known cycle.
+    /// Only these points can change with more iterations.
+    pub fn hit_limit(self) -> bool {
+        self == Escape::Interior { period: 0 }
+    }
+}
+
+/// Main cardioid and period-2 bulb of the Mandelbrot set, which together hold
+/// most of its interior area and can be answered without iterating.
+pub fn mandelbrot_known_period(cx: f64, cy: f64) -> Option<u32> {
+    let xq = cx - 0.25;
+    let q = xq * xq + cy * cy;
+    if q * (q + xq) <= 0.25 * cy * cy {
+        return Some(1);
+    }
+    if (cx + 1.0) * (cx + 1.0) + cy * cy <= 0.0625 {
+        return Some(2);
     }
-    iter
+    None
 }
 
-pub fn calculate_julia(zx: f64, zy: f64, cx: f64, cy: f64, max_iters: u32) -> u32 {
-    let mut x = zx;
-    let mut y = zy;
+/// Iterates `z -> step(z)` from `(x, y)` with Brent's cycle detection: the
+/// orbit is compared against a saved point that is refreshed at power-of-two
+/// intervals, so bounded orbits stop as soon as they repeat.
+fn iterate(mut x: f64, mut y: f64, max_iters: u32, step: impl Fn(f64, f64) -> (f64, f64)) -> Escape {
     let mut iter = 0;
+    let (mut saved_x, mut saved_y) = (x, y);
+    let mut power = 1;
+    let mut lambda = 0;
 
-    while (x * x + y * y <= 4.0) && (iter < max_iters) {
-        let x_temp = x * x - y * y + cx;
-        y = 2.0 * x * y + cy;
-        x = x_temp;
+    while x * x + y * y <= 4.0 {
+        if iter >= max_iters {
+            return Escape::Interior { period: 0 };
+        }
+
+        (x, y) = step(x, y);
         iter += 1;
+        lambda += 1;
+
+        if (x - saved_x).abs() < PERIOD_EPSILON && (y - saved_y).abs() < PERIOD_EPSILON {
+            return Escape::Interior { period: lambda };
+        }
+        if lambda == power {
+            (saved_x, saved_y) = (x, y);
+            power *= 2;
+            lambda = 0;
+        }
+    }
+    Escape::Escaped(iter)
+}
+
+pub fn calculate_mandelbrot(cx: f64, cy: f64, max_iters: u32) -> Escape {
+    if let Some(period) = mandelbrot_known_period(cx, cy) {
+        return Escape::Interior { period };
     }
-    iter
-}
\ No newline at end of file
+    iterate(0.0, 0.0, max_iters, |x, y| (x * x - y * y + cx, 2.0 * x * y + cy))
+}
+
+pub fn calculate_burning_ship(cx: f64, cy: f64, max_iters: u32) -> Escape {
+    iterate(0.0, 0.0, max_iters, |x, y| (x * x - y * y + cx, 2.0 * x.abs() * y.abs() + cy))
+}
+
+pub fn calculate_julia(zx: f64, zy: f64, cx: f64, cy: f64, max_iters: u32) -> Escape {
+    iterate(zx, zy, max_iters, |x, y| (x * x - y * y + cx, 2.0 * x * y + cy))
+}
@@ -0,0 +1,140 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::cli::ZoomArgs;
+use crate::config::Defaults;
+use crate::export::{parse_size, render_image, timestamp, ExportJob};
+use crate::gif::GifWriter;
+use crate::graphics::encode_png;
+use crate::hooks::FractalPane;
+
+/// Frame time of the animated GIF in hundredths of a second (25 fps).
+const GIF_DELAY: u16 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub center_x: f64,
+    pub center_y: f64,
+    pub scale: f64,
+}
+
+impl Keyframe {
+    pub fn of(pane: &FractalPane) -> Self {
+        Self { center_x: pane.center_x, center_y: pane.center_y, scale: pane.scale }
+    }
+}
+
+/// Camera move between two views: the scale changes exponentially so every
+/// frame zooms by the same factor, and the center follows the zoom progress
+/// so the target drifts into place at a steady on-screen speed.
+#[derive(Clone, Copy, Debug)]
+pub struct ZoomPath {
+    pub from: Keyframe,
+    pub to: Keyframe,
+}
+
+impl ZoomPath {
+    pub fn at(&self, t: f64) -> Keyframe {
+        let (a, b) = (self.from, self.to);
+        let scale = a.scale * (b.scale / a.scale).powf(t);
+        let weight = if (a.scale - b.scale).abs() > a.scale * 1e-9 {
+            (a.scale - scale) / (a.scale - b.scale)
+        } else {
+            // Pure pan: smoothstep eases in and out.
+            t * t * (3.0 - 2.0 * t)
+        };
+        Keyframe {
+            center_x: a.center_x + (b.center_x - a.center_x) * weight,
+            center_y: a.center_y + (b.center_y - a.center_y) * weight,
+            scale,
+        }
+    }
+
+    /// The view of frame `index` out of `frames`, first and last included.
+    pub fn frame(&self, index: u32, frames: u32) -> Keyframe {
+        if frames <= 1 {
+            return self.to;
+        }
+        self.at(index as f64 / (frames - 1) as f64)
+    }
+}
+
+/// Renders `frames` views of `pane` along `path` to `dir/frame-NNNNN.png`, and
+/// to `dir/zoom.gif` when `gif` is set. Auto-iteration panes get a budget
+/// matching each frame's depth.
+pub fn write_frames(
+    pane: &FractalPane,
+    path: ZoomPath,
+    frames: u32,
+    (width, height): (u32, u32),
+    gif: bool,
+    dir: &Path,
+    frames_done: &AtomicU32,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut animation = if gif {
+        Some(GifWriter::new(BufWriter::new(File::create(dir.join("zoom.gif"))?), width, height)?)
+    } else {
+        None
+    };
+
+    let mut frame = pane.clone();
+    for index in 0..frames {
+        let view = path.frame(index, frames);
+        frame.center_x = view.center_x;
+        frame.center_y = view.center_y;
+        frame.scale = view.scale;
+        if frame.auto_iters {
+            frame.max_iters = frame.auto_max_iters();
+        }
+
+        let rgb = render_image(&frame, width, height, &AtomicU32::new(0));
+        fs::write(dir.join(format!("frame-{:05}.png", index)), encode_png(&rgb, width, height)?)?;
+        if let Some(animation) = animation.as_mut() {
+            animation.write_frame(&rgb, GIF_DELAY)?;
+        }
+        frames_done.store(index + 1, Ordering::Relaxed);
+    }
+
+    match animation {
+        Some(animation) => animation.finish(),
+        None => Ok(()),
+    }
+}
+
+/// Parses the TUI prompt: `FRAMES WIDTHxHEIGHT`, optionally followed by `gif`.
+pub fn parse_zoom_spec(s: &str) -> Option<(u32, (u32, u32), bool)> {
+    let mut parts = s.split_whitespace();
+    let frames = parts.next()?.parse().ok().filter(|&n| n > 0)?;
+    let size = parse_size(parts.next()?)?;
+    let gif = match parts.next() {
+        None => false,
+        Some(word) if word.eq_ignore_ascii_case("gif") => true,
+        Some(_) => return None,
+    };
+    Some((frames, size, gif))
+}
+
+/// Zooms from the default view into the pane's current one, in the background.
+pub fn spawn_zoom(pane: &FractalPane, defaults: &Defaults, frames: u32, size: (u32, u32), gif: bool) -> ExportJob {
+    let from = Keyframe { center_x: defaults.center_x, center_y: defaults.center_y, scale: defaults.scale };
+    let path = ZoomPath { from, to: Keyframe::of(pane) };
+    let pane = pane.clone();
+    let dir = PathBuf::from(format!("fractal-{}-zoom", timestamp()));
+    ExportJob::spawn(dir, frames, move |dir, frames_done| {
+        write_frames(&pane, path, frames, size, gif, dir, frames_done)
+    })
+}
+
+/// Entry point of `frac-tui-rs zoom`.
+pub fn zoom_headless(args: &ZoomArgs, defaults: &Defaults) -> io::Result<()> {
+    let pane = args.view.pane(defaults);
+    let from = Keyframe { center_x: args.from.0, center_y: args.from.1, scale: args.from_scale };
+    let path = ZoomPath { from, to: Keyframe::of(&pane) };
+    write_frames(&pane, path, args.frames, args.size, args.gif, &args.out_dir, &AtomicU32::new(0))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", args.out_dir.display(), e)))?;
+    eprintln!("wrote {} frames to {}", args.frames, args.out_dir.display());
+    Ok(())
+}